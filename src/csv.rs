@@ -3,15 +3,205 @@
 
 #![warn(missing_docs)]
 
-use std::{error, io};
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    error, fmt, io, panic,
+    str::FromStr,
+    thread,
+};
 
-use csv::{ReaderBuilder, Trim};
+pub use csv::QuoteStyle;
+use csv::{ReaderBuilder, StringRecord, Trim, WriterBuilder};
 use log::warn;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
 
-use crate::{Tx, Txs};
+use crate::{
+    Account, Cid, DisputeState, EngineSnapshot, Error, IdField, Tx, TxKind, Txid, Txs,
+    MAX_DECIMAL_PLACES,
+};
+
+/// Represents an incoming transaction row before its IDs are range-checked.
+///
+/// IDs are read using a wider type than `Cid` and `Txid`,
+/// so that out of range IDs can be reported with `Error::IdOutOfRange`.
+#[derive(Debug, Deserialize)]
+pub(crate) struct TxRecord {
+    #[serde(rename = "type")]
+    kind: TxKind,
+    client: u64,
+    tx: u64,
+    amount: Option<Decimal>,
+}
+
+impl TxRecord {
+    /// Converts this record into a `Tx`, checking that its IDs are in range.
+    pub(crate) fn into_tx(self) -> Result<Tx, Error> {
+        let cid = Cid::try_from(self.client).map_err(|_| Error::IdOutOfRange(IdField::Client))?;
+        let txid = Txid::try_from(self.tx).map_err(|_| Error::IdOutOfRange(IdField::Tx))?;
+        Ok(Tx::new(self.kind, cid, txid, self.amount))
+    }
+}
+
+/// Parses a single CSV row without a header, _e.g._, `"deposit, 1, 1001, 5.0"`,
+/// whose columns are those of [`INPUT_HEADER`].
+///
+/// The amount can be omitted for disputes, resolves and chargebacks.
+/// A row that cannot be parsed is rejected with `Error::InvalidTx`.
+///
+/// # Examples
+///
+/// ```
+/// use toy_payments_engine::*;
+/// use rust_decimal_macros::dec;
+///
+/// assert_eq!("deposit, 1, 1001, 5.0".parse(), Ok(Tx::deposit(1, 1001, dec!(5.0))));
+/// assert_eq!("dispute, 1, 1001".parse(), Ok(Tx::dispute(1, 1001)));
+/// assert_eq!("refund, 1, 1001".parse::<Tx>(), Err(Error::InvalidTx));
+/// ```
+impl FromStr for Tx {
+    type Err = Error;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let mut reader = ReaderBuilder::new()
+            .has_headers(false)
+            .trim(Trim::All)
+            .flexible(true)
+            .from_reader(line.as_bytes());
+        let mut rows = reader.records();
+        match (rows.next(), rows.next()) {
+            (Some(Ok(row)), None) if (3..=INPUT_HEADER.len()).contains(&row.len()) => {
+                let amount = match row.get(3).unwrap_or_default() {
+                    "" => None,
+                    amount => Some(Decimal::from_str(amount).map_err(|_| Error::InvalidTx)?),
+                };
+                TxRecord {
+                    kind: row[0].parse()?,
+                    client: row[1].parse().map_err(|_| Error::InvalidTx)?,
+                    tx: row[2].parse().map_err(|_| Error::InvalidTx)?,
+                    amount,
+                }
+                .into_tx()
+            }
+            _ => Err(Error::InvalidTx),
+        }
+    }
+}
+
+/// Represents a transaction that was rejected while processing a CSV buffer.
+#[derive(Debug, PartialEq)]
+pub struct LineError {
+    /// The line number of the rejected transaction.
+    ///
+    /// For CSV input this is the 1-based line in the file, so the header is line 1
+    /// and the first transaction is line 2.
+    pub line: usize,
+    /// The reason the transaction was rejected.
+    pub error: Error,
+    /// The text of the rejected row, without its line terminator.
+    ///
+    /// For CSV input this is rebuilt from the untrimmed fields of the row
+    /// joined with the delimiter, so quotes are not preserved.
+    pub raw: String,
+}
+
+/// Represents a CSV row that could not be read, _e.g._, because of an I/O error
+/// or invalid UTF-8, aborting the processing of a CSV buffer.
+#[derive(Debug)]
+pub struct CsvError {
+    /// The line number of the unreadable row.
+    pub line: usize,
+    /// The underlying parsing error.
+    pub source: csv::Error,
+}
+
+impl fmt::Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Error in line {}: {}", self.line, self.source)
+    }
+}
+
+impl error::Error for CsvError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// Represents the outcome of processing a CSV buffer of transactions.
+#[derive(Debug, Default, PartialEq)]
+pub struct ProcessReport {
+    /// The number of transactions read, whether accepted or rejected.
+    pub processed: usize,
+    /// The number of transactions that were accepted.
+    pub accepted: usize,
+    /// The number of transactions that were rejected.
+    pub rejected: usize,
+    /// The number of rejected transactions for each kind of error.
+    pub by_error: HashMap<Error, usize>,
+    /// The transactions that were rejected, in the order they were read.
+    pub errors: Vec<LineError>,
+}
+
+impl ProcessReport {
+    /// Records an accepted transaction.
+    pub(crate) fn accept(&mut self) {
+        self.processed += 1;
+        self.accepted += 1;
+    }
+
+    /// Records a rejected transaction, logging it as a warning.
+    pub(crate) fn reject(&mut self, line: usize, raw: String, error: Error) {
+        warn!("Warning in line {}: {} in `{}`", line, error, raw);
+        self.processed += 1;
+        self.rejected += 1;
+        *self.by_error.entry(error.clone()).or_default() += 1;
+        self.errors.push(LineError { line, error, raw });
+    }
+
+    /// Adds the counts and errors of `other` into this report.
+    fn merge(&mut self, other: ProcessReport) {
+        self.processed += other.processed;
+        self.accepted += other.accepted;
+        self.rejected += other.rejected;
+        for (error, count) in other.by_error {
+            *self.by_error.entry(error).or_default() += count;
+        }
+        self.errors.extend(other.errors);
+    }
+}
+
+/// Options to customize how transactions are read by [`process_transactions_with`].
+#[derive(Debug, Clone)]
+pub struct ReadOptions {
+    /// Whether the first row is a header row naming the columns.
+    /// When unset, every row is a transaction whose columns are, in order,
+    /// `type`, `client`, `tx` and `amount`.
+    /// Defaults to `true`.
+    pub has_headers: bool,
+    /// The field delimiter, _e.g._, `b';'` for semicolon-separated files.
+    /// Defaults to `b','`.
+    pub delimiter: u8,
+}
+
+impl Default for ReadOptions {
+    fn default() -> Self {
+        Self {
+            has_headers: true,
+            delimiter: b',',
+        }
+    }
+}
 
 /// Parses and processes incoming transactions from a file.
 ///
+/// Processing is aborted with a [`CsvError`] if a row cannot be read.
+/// Rows that cannot be parsed, _e.g._, with a non-numeric amount,
+/// are skipped and reported as `Error::InvalidTx`.
+/// Transactions that cannot be processed are skipped and logged as warnings.
+/// Along with the resulting `Txs`, a `ProcessReport` is returned
+/// describing every skipped transaction.
+/// A leading UTF-8 byte order mark, as written by some spreadsheet exports, is ignored.
+///
 /// # Examples
 ///
 /// ```
@@ -30,30 +220,579 @@ use crate::{Tx, Txs};
 /// chargeback, 1, 1
 /// ";
 ///
-/// let txs = process_transactions(data.as_bytes()).unwrap();
+/// let (txs, report) = process_transactions(data.as_bytes()).unwrap();
+/// assert_eq!(
+///     report.errors,
+///     vec![LineError {
+///         line: 6,
+///         error: toy_payments_engine::Error::InsuffienctFunds,
+///         raw: "withdrawal, 2, 5, 3.0".to_string(),
+///     }]
+/// );
+/// ```
+pub fn process_transactions<R: io::Read>(
+    rdr: R,
+) -> Result<(Txs, ProcessReport), Box<dyn error::Error>> {
+    process_transactions_with(rdr, &ReadOptions::default())
+}
+
+/// Parses and processes incoming transactions from a file using custom `options`,
+/// _e.g._, a file without a header row.
+///
+/// Transactions are processed as in [`process_transactions`].
+///
+/// # Examples
+///
+/// ```
+/// use toy_payments_engine::csv::*;
+/// use rust_decimal_macros::dec;
+///
+/// let data = "\
+/// deposit, 1, 1, 1.0
+/// withdrawal, 1, 2, 0.25
+/// ";
+///
+/// let options = ReadOptions {
+///     has_headers: false,
+///     ..ReadOptions::default()
+/// };
+/// let (txs, report) = process_transactions_with(data.as_bytes(), &options).unwrap();
+/// assert_eq!(report.processed, 2);
+/// assert_eq!(txs.get(1).unwrap().available, dec!(0.75));
+/// ```
+pub fn process_transactions_with<R: io::Read>(
+    rdr: R,
+    options: &ReadOptions,
+) -> Result<(Txs, ProcessReport), Box<dyn error::Error>> {
+    let mut txs = Txs::new();
+    let mut report = ProcessReport::default();
+    process_into(rdr, options, &mut txs, &mut report, |_, _| {}, |_| {})?;
+    Ok((txs, report))
+}
+
+/// Parses and processes incoming transactions from a file,
+/// calling `on_row` with the line number of every row after it is processed,
+/// _e.g._, to report the progress of a long import.
+///
+/// `on_row` is called for both accepted and rejected transactions,
+/// which are otherwise processed as in [`process_transactions`].
+///
+/// # Examples
+///
+/// ```
+/// use toy_payments_engine::csv::*;
+///
+/// let data = "\
+/// type, client, tx, amount
+/// deposit, 1, 1, 1.0
+/// withdrawal, 1, 2, 5.0
+/// ";
+///
+/// let mut rows = 0;
+/// process_transactions_with_progress(data.as_bytes(), |_| rows += 1).unwrap();
+/// assert_eq!(rows, 2);
+/// ```
+pub fn process_transactions_with_progress<R: io::Read, F: FnMut(usize)>(
+    rdr: R,
+    on_row: F,
+) -> Result<(Txs, ProcessReport), Box<dyn error::Error>> {
+    let mut txs = Txs::new();
+    let mut report = ProcessReport::default();
+    process_into(
+        rdr,
+        &ReadOptions::default(),
+        &mut txs,
+        &mut report,
+        |_, _| {},
+        on_row,
+    )?;
+    Ok((txs, report))
+}
+
+/// Parses and processes incoming transactions from several files in sequence,
+/// _e.g._, a transaction log rotated into multiple files.
+///
+/// Every file must start with its own header row.
+/// All files are processed into a single `Txs`,
+/// so a transaction in a file can be disputed in a later one,
+/// and transaction IDs must be unique across all files.
+/// The line numbers in the returned `ProcessReport` are relative to each file.
+///
+/// # Examples
+///
+/// ```
+/// use toy_payments_engine::csv::*;
+/// use rust_decimal_macros::dec;
+///
+/// let first = "type, client, tx, amount\ndeposit, 1, 1, 5.0\n";
+/// let second = "type, client, tx, amount\ndispute, 1, 1,\n";
+///
+/// let (txs, _) = process_transactions_many([first.as_bytes(), second.as_bytes()]).unwrap();
+/// assert_eq!(txs.get(1).unwrap().held, dec!(5));
+/// ```
+pub fn process_transactions_many<R: io::Read>(
+    readers: impl IntoIterator<Item = R>,
+) -> Result<(Txs, ProcessReport), Box<dyn error::Error>> {
+    let mut txs = Txs::new();
+    let mut report = ProcessReport::default();
+    for rdr in readers {
+        process_into(
+            rdr,
+            &ReadOptions::default(),
+            &mut txs,
+            &mut report,
+            |_, _| {},
+            |_| {},
+        )?;
+    }
+    Ok((txs, report))
+}
+
+/// Parses and processes incoming transactions from a file,
+/// forwarding every account change to `persist` as it happens,
+/// _e.g._, to write through to an external store.
+///
+/// After each successful transaction, `persist` is called with the client ID
+/// and the updated account. Skipped transactions do not trigger `persist`.
+/// Otherwise, transactions are processed as in [`process_transactions`].
+///
+/// # Examples
+///
+/// ```
+/// use toy_payments_engine::csv::*;
+/// use rust_decimal_macros::dec;
+///
+/// let data = "\
+/// type, client, tx, amount
+/// deposit, 1, 1, 1.0
+/// withdrawal, 1, 2, 5.0
+/// ";
+///
+/// let mut store = vec![];
+/// process_transactions_persisting(data.as_bytes(), |cid, account| {
+///     store.push((cid, account.available));
+/// })
+/// .unwrap();
+/// assert_eq!(store, vec![(1, dec!(1.0))]);
+/// ```
+pub fn process_transactions_persisting<R: io::Read, F: FnMut(Cid, &Account)>(
+    rdr: R,
+    persist: F,
+) -> Result<(Txs, ProcessReport), Box<dyn error::Error>> {
+    let mut txs = Txs::new();
+    let mut report = ProcessReport::default();
+    process_into(
+        rdr,
+        &ReadOptions::default(),
+        &mut txs,
+        &mut report,
+        persist,
+        |_| {},
+    )?;
+    Ok((txs, report))
+}
+
+/// Parses and processes incoming transactions from every CSV file in a zip archive,
+/// _e.g._, a daily dump of several transaction logs.
+///
+/// Entries whose name ends with `.csv` are processed in name order into a single `Txs`,
+/// as in [`process_transactions_many`]; any other entry is skipped.
+/// The line numbers in the returned `ProcessReport` are relative to each entry.
+///
+/// Requires the `zip` feature.
+#[cfg(feature = "zip")]
+pub fn process_transactions_zip<R: io::Read + io::Seek>(
+    rdr: R,
+) -> Result<(Txs, ProcessReport), Box<dyn error::Error>> {
+    let mut archive = zip::ZipArchive::new(rdr)?;
+    let mut names: Vec<String> = archive
+        .file_names()
+        .filter(|name| name.to_ascii_lowercase().ends_with(".csv"))
+        .map(String::from)
+        .collect();
+    names.sort();
+
+    let mut txs = Txs::new();
+    let mut report = ProcessReport::default();
+    for name in names {
+        process_into(
+            archive.by_name(&name)?,
+            &ReadOptions::default(),
+            &mut txs,
+            &mut report,
+            |_, _| {},
+            |_| {},
+        )?;
+    }
+    Ok((txs, report))
+}
+
+/// Parses and processes incoming transactions from a gzip-compressed file,
+/// _e.g._, an archived `.csv.gz` transaction log.
+///
+/// The file is decompressed while being read,
+/// and transactions are processed as in [`process_transactions`].
+///
+/// Requires the `gzip` feature.
+#[cfg(feature = "gzip")]
+pub fn process_transactions_gz<R: io::Read>(
+    rdr: R,
+) -> Result<(Txs, ProcessReport), Box<dyn error::Error>> {
+    process_transactions(flate2::read::GzDecoder::new(rdr))
+}
+
+/// Parses and processes incoming transactions from a file using `threads` worker threads.
+///
+/// Transactions of different clients never interact,
+/// so the rows are sharded by client ID and every shard is processed on its own thread,
+/// preserving the order of the transactions of each client.
+/// The resulting `Txs` and `ProcessReport` are those of [`process_transactions`],
+/// except for the handling of transaction IDs across clients.
+///
+/// Transaction IDs are still unique globally:
+/// a transaction ID belongs to the first client using it for a deposit or withdrawal,
+/// and a deposit or withdrawal of any other client reusing it is rejected with `Error::TxAlreadyExists`,
+/// even if the transaction of the first client was itself rejected.
+/// A dispute, resolve or chargeback referring to a transaction of another client
+/// is rejected with `Error::TxNotFound` instead of `Error::CidMismatch`.
+///
+/// # Examples
+///
+/// ```
+/// use toy_payments_engine::csv::*;
+/// use rust_decimal_macros::dec;
+///
+/// let data = "\
+/// type, client, tx, amount
+/// deposit, 1, 1, 1.0
+/// deposit, 2, 2, 2.0
+/// deposit, 2, 1, 3.0
+/// withdrawal, 1, 3, 0.5
+/// ";
+///
+/// let (txs, report) = process_transactions_parallel(data.as_bytes(), 2).unwrap();
+/// assert_eq!(report.processed, 4);
+/// assert_eq!(report.errors[0].error, toy_payments_engine::Error::TxAlreadyExists);
+/// assert_eq!(txs.get(1).unwrap().available, dec!(0.5));
+/// assert_eq!(txs.get(2).unwrap().available, dec!(2.0));
 /// ```
-pub fn process_transactions<R: io::Read>(rdr: R) -> Result<Txs, Box<dyn error::Error>> {
+pub fn process_transactions_parallel<R: io::Read>(
+    rdr: R,
+    threads: usize,
+) -> Result<(Txs, ProcessReport), Box<dyn error::Error>> {
+    let threads = threads.max(1);
+    let mut report = ProcessReport::default();
+    let mut rows = 0;
+    let mut owners: HashMap<Txid, Cid> = HashMap::new();
+    let mut shards: Vec<Vec<(u64, usize, String, Tx)>> = vec![Vec::new(); threads];
+    read_records(rdr, &ReadOptions::default(), |lineno, raw, record| {
+        let seq = rows;
+        rows += 1;
+        let result = record.and_then(TxRecord::into_tx).and_then(|tx| {
+            let owner = match tx.kind {
+                TxKind::Deposit | TxKind::Withdrawal => *owners.entry(tx.txid).or_insert(tx.cid),
+                _ => tx.cid,
+            };
+            if owner == tx.cid {
+                Ok(tx)
+            } else {
+                Err(Error::TxAlreadyExists)
+            }
+        });
+        match result {
+            Ok(tx) => shards[tx.cid as usize % threads].push((seq, lineno, raw, tx)),
+            Err(err) => report.reject(lineno, raw, err),
+        }
+    })?;
+
+    let results: Vec<(Txs, ProcessReport)> = thread::scope(|scope| {
+        let handles: Vec<_> = shards
+            .into_iter()
+            .map(|shard| {
+                scope.spawn(|| {
+                    let mut txs = Txs::new();
+                    let mut report = ProcessReport::default();
+                    for (seq, lineno, raw, tx) in shard {
+                        txs.next_seq = seq;
+                        match txs.process_tx(tx) {
+                            Ok(()) => report.accept(),
+                            Err(err) => report.reject(lineno, raw, err),
+                        }
+                    }
+                    (txs, report)
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|err| panic::resume_unwind(err))
+            })
+            .collect()
+    });
+
+    let mut txs = Txs::new();
+    for (shard, shard_report) in results {
+        txs.accounts.extend(shard.accounts);
+        txs.txs.extend(shard.txs);
+        txs.seen_txids.extend(shard.seen_txids);
+        txs.max_txid = txs.max_txid.max(shard.max_txid);
+        report.merge(shard_report);
+    }
+    txs.next_seq = rows;
+    report.errors.sort_by_key(|error| error.line);
+    Ok((txs, report))
+}
+
+/// Parses and processes incoming transactions from `rdr` into `txs` according to `options`,
+/// recording the skipped transactions in `report`,
+/// calling `persist` with the updated account of every successful one,
+/// and calling `on_row` with the line number of every processed one.
+fn process_into<R: io::Read, F: FnMut(Cid, &Account), G: FnMut(usize)>(
+    rdr: R,
+    options: &ReadOptions,
+    txs: &mut Txs,
+    report: &mut ProcessReport,
+    mut persist: F,
+    mut on_row: G,
+) -> Result<(), Box<dyn error::Error>> {
+    read_records(rdr, options, |lineno, raw, record| {
+        let result = record.and_then(TxRecord::into_tx).and_then(|tx| {
+            let cid = tx.cid();
+            txs.process_tx(tx).map(|()| cid)
+        });
+        match result {
+            Ok(cid) => {
+                report.accept();
+                if let Some(account) = txs.get(cid) {
+                    persist(cid, account);
+                }
+            }
+            Err(err) => report.reject(lineno, raw, err),
+        }
+        on_row(lineno);
+    })
+}
+
+/// Parses every row from `rdr` according to `options`,
+/// calling `f` with its line number, untrimmed fields joined with the delimiter,
+/// and parsed record, or `Error::InvalidTx` if the row cannot be parsed.
+///
+/// The fields are joined again since `rdr` is streamed and the original row text is not kept.
+fn read_records<R: io::Read, F: FnMut(usize, String, Result<TxRecord, Error>)>(
+    rdr: R,
+    options: &ReadOptions,
+    mut f: F,
+) -> Result<(), Box<dyn error::Error>> {
     let mut reader = ReaderBuilder::new()
-        .trim(Trim::All)
+        .has_headers(options.has_headers)
+        .delimiter(options.delimiter)
         .flexible(true)
         .from_reader(rdr);
+    let headers = if options.has_headers {
+        let mut headers = reader.headers()?.clone();
+        headers.trim();
+        headers
+    } else {
+        StringRecord::from(INPUT_HEADER.to_vec())
+    };
+    let first_line = if options.has_headers { 2 } else { 1 };
+    for (lineno, result) in (first_line..).zip(reader.records()) {
+        let to_csv_error = |source| CsvError {
+            line: lineno,
+            source,
+        };
+        let mut row = result.map_err(to_csv_error)?;
+        let raw = row
+            .iter()
+            .collect::<Vec<_>>()
+            .join(&char::from(options.delimiter).to_string());
+        row.trim();
+        let record = row
+            .deserialize(Some(&headers))
+            .map_err(|_| Error::InvalidTx);
+        f(lineno, raw, record);
+    }
+
+    Ok(())
+}
+
+/// Parses and processes incoming transactions from a file,
+/// returning the final state of every client's account.
+///
+/// Transactions that cannot be processed are skipped, as in [`process_transactions`].
+///
+/// # Examples
+///
+/// ```
+/// use toy_payments_engine::*;
+/// use toy_payments_engine::csv::*;
+/// use rust_decimal_macros::dec;
+///
+/// let data = "\
+/// type, client, tx, amount
+/// deposit, 1, 1, 1.0
+/// withdrawal, 1, 2, 0.5
+/// ";
+///
+/// let accounts = process_to_map(data.as_bytes()).unwrap();
+/// assert_eq!(accounts.get(&1), Some(&Account::new(dec!(0.5), dec!(0), false)));
+/// ```
+pub fn process_to_map<R: io::Read>(rdr: R) -> Result<HashMap<Cid, Account>, Box<dyn error::Error>> {
+    let (txs, _) = process_transactions(rdr)?;
+    Ok(txs.accounts)
+}
+
+/// Represents an opening account state row, as read by [`import_accounts`].
+#[derive(Debug, Deserialize)]
+struct AccountRow {
+    client: Cid,
+    available: Decimal,
+    held: Decimal,
+    locked: bool,
+}
+
+/// Imports opening account states from a CSV buffer.
+///
+/// Each row describes a client's account with the columns
+/// `client`, `available`, `held` and `locked`.
+/// Accounts are seeded directly into the returned `Txs`,
+/// without processing any transaction.
+/// Both `available` and `held` must be non-negative,
+/// and a client cannot appear more than once.
+///
+/// # Examples
+///
+/// ```
+/// use toy_payments_engine::*;
+/// use toy_payments_engine::csv::*;
+/// use rust_decimal_macros::dec;
+///
+/// let data = "\
+/// client, available, held, locked
+/// 1, 10.5, 0, false
+/// 2, 0, 3, true
+/// ";
+///
+/// let txs = import_accounts(data.as_bytes()).unwrap();
+/// assert_eq!(txs.get(1), Some(&Account::new(dec!(10.5), dec!(0), false)));
+/// assert_eq!(txs.get(2), Some(&Account::new(dec!(0), dec!(3), true)));
+/// ```
+pub fn import_accounts<R: io::Read>(rdr: R) -> Result<Txs, Box<dyn error::Error>> {
+    let mut reader = ReaderBuilder::new().trim(Trim::All).from_reader(rdr);
     let mut txs = Txs::new();
-    let mut lineno = 1;
     for result in reader.deserialize() {
-        let tx: Tx = result?;
-        if let Err(err) = txs.process_tx(tx) {
-            warn!("Warning in line {}: {:?}", lineno, err);
+        let row: AccountRow = result?;
+        if row.available < Decimal::ZERO || row.held < Decimal::ZERO {
+            return Err(format!("Negative opening balance for client {}", row.client).into());
+        }
+        if let Entry::Vacant(entry) = txs.accounts.entry(row.client) {
+            entry.insert(Account::new(row.available, row.held, row.locked));
+        } else {
+            return Err(format!("Duplicate opening balance for client {}", row.client).into());
         }
-        lineno += 1;
     }
 
     Ok(txs)
 }
 
+/// Represents a client's account row, as written by [`write_transactions`].
+///
+/// The field order matches the columns in [`OUTPUT_HEADER`].
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct AccountRecord {
+    client: Cid,
+    available: Decimal,
+    held: Decimal,
+    total: Decimal,
+    locked: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    owed: Option<Decimal>,
+}
+
+/// Represents a client's account row with its `locked` flag formatted by a [`BoolFormat`].
+#[derive(Debug, Serialize)]
+struct FormattedAccountRecord {
+    client: Cid,
+    available: Decimal,
+    held: Decimal,
+    total: Decimal,
+    locked: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    owed: Option<Decimal>,
+}
+
+/// The columns of an incoming transaction row, as named in the header row
+/// read by [`process_transactions`].
+pub const INPUT_HEADER: [&str; 4] = ["type", "client", "tx", "amount"];
+
+/// The header row written by [`write_transactions`].
+pub const OUTPUT_HEADER: [&str; 5] = ["client", "available", "held", "total", "locked"];
+
+/// Represents how boolean columns, _e.g._, `locked`, are written.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum BoolFormat {
+    /// Written as `true` or `false`.
+    #[default]
+    TrueFalse,
+    /// Written as `1` or `0`.
+    OneZero,
+    /// Written as `yes` or `no`.
+    YesNo,
+}
+
+impl BoolFormat {
+    /// Returns the text of `value` in this format.
+    fn format(self, value: bool) -> &'static str {
+        match (self, value) {
+            (BoolFormat::TrueFalse, true) => "true",
+            (BoolFormat::TrueFalse, false) => "false",
+            (BoolFormat::OneZero, true) => "1",
+            (BoolFormat::OneZero, false) => "0",
+            (BoolFormat::YesNo, true) => "yes",
+            (BoolFormat::YesNo, false) => "no",
+        }
+    }
+}
+
+/// Options to customize how accounts are written by [`write_transactions_with`].
+#[derive(Debug, Clone)]
+pub struct WriteOptions {
+    /// When set, an extra `owed` column is written.
+    /// Accounts with a negative `available` balance are written with
+    /// `available` as `0` and its absolute value in the `owed` column.
+    /// Accounts with a non-negative `available` balance have `owed` as `0`.
+    pub split_owed: bool,
+    /// The quoting style used for every field.
+    /// Defaults to `QuoteStyle::Necessary`.
+    pub quote_style: QuoteStyle,
+    /// How the `locked` column is written.
+    /// Defaults to `BoolFormat::TrueFalse`.
+    pub bool_format: BoolFormat,
+    /// When set, accounts for which `Account::is_empty` holds are not written.
+    /// Locked accounts are always written, even with no funds.
+    pub skip_empty: bool,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        Self {
+            split_owed: false,
+            quote_style: QuoteStyle::Necessary,
+            bool_format: BoolFormat::TrueFalse,
+            skip_empty: false,
+        }
+    }
+}
+
 /// Write transactions `txs` to a `Write`r `wtr`.
 /// These transactions are written in CSV format.
 /// The first row contains a header row to indicate column names.
-/// Note that there is no particular order when writing the client's accounts.
+/// The client's accounts are written sorted by client ID in ascending order.
+/// The `available`, `held` and `total` funds are rounded to `MAX_DECIMAL_PLACES` decimal places,
+/// using the banker's rounding strategy of `Decimal::round_dp`.
 ///
 /// # Examples
 ///
@@ -79,35 +818,643 @@ pub fn process_transactions<R: io::Read>(rdr: R) -> Result<Txs, Box<dyn error::E
 /// );
 /// ```
 pub fn write_transactions<W: io::Write>(txs: &Txs, wtr: W) -> Result<(), Box<dyn error::Error>> {
-    let mut writer = csv::Writer::from_writer(wtr);
+    write_transactions_with(txs, wtr, &WriteOptions::default())
+}
 
-    writer.write_record(&["client", "available", "held", "total", "locked"])?;
+/// Write transactions `txs` to a `Write`r `wtr` using the given `options`.
+///
+/// Returns `Error::MathError` if the total funds of an account overflow.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::*;
+/// use toy_payments_engine::*;
+/// use toy_payments_engine::csv::*;
+/// use rust_decimal_macros::dec;
+///
+/// let mut txs = Txs::new();
+/// let mut buf = vec![];
+///
+/// txs.deposit(1, 1001, dec!(10)).unwrap();
+///
+/// let options = WriteOptions {
+///     split_owed: true,
+///     ..Default::default()
+/// };
+/// write_transactions_with(&txs, BufWriter::new(&mut buf), &options).unwrap();
+///
+/// assert_eq!(
+///     std::str::from_utf8(&buf).unwrap(),
+///     "client,available,held,total,locked,owed
+/// 1,10,0,10,false,0
+/// "
+/// );
+/// ```
+pub fn write_transactions_with<W: io::Write>(
+    txs: &Txs,
+    wtr: W,
+    options: &WriteOptions,
+) -> Result<(), Box<dyn error::Error>> {
+    write_accounts(txs.accounts(), wtr, options)
+}
 
-    for (cid, account) in &txs.accounts {
-        let total = account.available + account.held;
-        writer.write_record(&[
-            cid.to_string(),
-            account.available.to_string(),
-            account.held.to_string(),
-            total.to_string(),
-            account.locked.to_string(),
-        ])?;
-    }
+/// Write transactions `txs` to every `Write`r in `sinks` at once,
+/// _e.g._, to both a file and stdout, without buffering the whole output.
+///
+/// Each sink receives the same content as written by [`write_transactions`].
+///
+/// # Examples
+///
+/// ```
+/// use toy_payments_engine::*;
+/// use toy_payments_engine::csv::*;
+/// use rust_decimal_macros::dec;
+///
+/// let mut txs = Txs::new();
+/// let (mut first, mut second) = (vec![], vec![]);
+///
+/// txs.deposit(1, 1001, dec!(10)).unwrap();
+///
+/// write_transactions_tee(&txs, &mut [&mut first, &mut second]).unwrap();
+///
+/// assert_eq!(first, second);
+/// ```
+pub fn write_transactions_tee(
+    txs: &Txs,
+    sinks: &mut [&mut dyn io::Write],
+) -> Result<(), Box<dyn error::Error>> {
+    write_transactions(txs, Tee { sinks })
+}
 
-    writer.flush()?;
-    Ok(())
+/// A `Write`r that forwards every write to all of its `sinks`.
+struct Tee<'a, 'b> {
+    sinks: &'a mut [&'b mut dyn io::Write],
 }
 
-#[cfg(test)]
-mod tests {
+impl io::Write for Tee<'_, '_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for sink in self.sinks.iter_mut() {
+            sink.write_all(buf)?;
+        }
+        Ok(buf.len())
+    }
 
-    use std::io::BufWriter;
+    fn flush(&mut self) -> io::Result<()> {
+        for sink in self.sinks.iter_mut() {
+            sink.flush()?;
+        }
+        Ok(())
+    }
+}
 
+/// Write transactions `txs` to a `Write`r `wtr`, grouped by their locked status.
+///
+/// Unlocked accounts are written first, followed by locked accounts,
+/// each group sorted by client ID in ascending order.
+/// Otherwise, the rows are written in the same format as [`write_transactions`].
+///
+/// # Examples
+///
+/// ```
+/// use toy_payments_engine::*;
+/// use toy_payments_engine::csv::*;
+/// use rust_decimal_macros::dec;
+///
+/// let mut txs = Txs::new();
+/// let mut buf = vec![];
+///
+/// txs.deposit(1, 1001, dec!(10)).unwrap();
+/// txs.deposit(2, 1002, dec!(5)).unwrap();
+/// txs.freeze(1).unwrap();
+///
+/// write_transactions_grouped(&txs, &mut buf).unwrap();
+///
+/// assert_eq!(
+///     std::str::from_utf8(&buf).unwrap(),
+///     "client,available,held,total,locked
+/// 2,5,0,5,false
+/// 1,10,0,10,true
+/// "
+/// );
+/// ```
+pub fn write_transactions_grouped<W: io::Write>(
+    txs: &Txs,
+    wtr: W,
+) -> Result<(), Box<dyn error::Error>> {
+    let options = WriteOptions::default();
+    let mut records = account_records(txs.accounts(), options.split_owed)?;
+    records.sort_by_key(|record| record.locked);
+    write_records(records, wtr, &options)
+}
+
+/// Write only the accounts in `txs` that changed since the `since` snapshot to a `Write`r `wtr`.
+///
+/// An account is written if its `available`, `held` or `locked` state differs from the snapshot,
+/// or if the client did not exist when the snapshot was taken.
+/// The rows are written in the same format as [`write_transactions`].
+///
+/// # Examples
+///
+/// ```
+/// use toy_payments_engine::*;
+/// use toy_payments_engine::csv::*;
+/// use rust_decimal_macros::dec;
+///
+/// let mut txs = Txs::new();
+/// let mut buf = vec![];
+///
+/// txs.deposit(1, 1001, dec!(10)).unwrap();
+/// txs.deposit(2, 1002, dec!(10)).unwrap();
+/// let snapshot = txs.engine_snapshot();
+/// txs.withdrawal(2, 1003, dec!(4)).unwrap();
+///
+/// write_changed_transactions(&txs, &snapshot, &mut buf).unwrap();
+///
+/// assert_eq!(
+///     std::str::from_utf8(&buf).unwrap(),
+///     "client,available,held,total,locked
+/// 2,6,0,6,false
+/// "
+/// );
+/// ```
+pub fn write_changed_transactions<W: io::Write>(
+    txs: &Txs,
+    since: &EngineSnapshot,
+    wtr: W,
+) -> Result<(), Box<dyn error::Error>> {
+    let changed = txs.accounts().filter(|(cid, account)| {
+        since
+            .get(*cid)
+            .is_none_or(|snapshot| snapshot.differs_from(account))
+    });
+    write_accounts(changed, wtr, &WriteOptions::default())
+}
+
+fn write_accounts<'a, W: io::Write, I: Iterator<Item = (Cid, &'a Account)>>(
+    accounts: I,
+    wtr: W,
+    options: &WriteOptions,
+) -> Result<(), Box<dyn error::Error>> {
+    let accounts = accounts.filter(|(_, account)| !(options.skip_empty && account.is_empty()));
+    write_records(account_records(accounts, options.split_owed)?, wtr, options)
+}
+
+/// Writes the header row followed by the account `records`, in the given order.
+fn write_records<W: io::Write>(
+    records: Vec<AccountRecord>,
+    wtr: W,
+    options: &WriteOptions,
+) -> Result<(), Box<dyn error::Error>> {
+    let mut writer = WriterBuilder::new()
+        .has_headers(false)
+        .quote_style(options.quote_style)
+        .from_writer(wtr);
+
+    let mut header = OUTPUT_HEADER.to_vec();
+    if options.split_owed {
+        header.push("owed");
+    }
+    writer.write_record(&header)?;
+
+    for record in records {
+        writer.serialize(FormattedAccountRecord {
+            client: record.client,
+            available: record.available,
+            held: record.held,
+            total: record.total,
+            locked: options.bool_format.format(record.locked),
+            owed: record.owed,
+        })?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Builds the output records of the given accounts, sorted by client ID.
+///
+/// Balances are rounded to `MAX_DECIMAL_PLACES` decimal places, without trailing zeros.
+/// When `split_owed` is set, negative available balances are reported as owed instead.
+pub(crate) fn account_records<'a, I: Iterator<Item = (Cid, &'a Account)>>(
+    accounts: I,
+    split_owed: bool,
+) -> Result<Vec<AccountRecord>, Error> {
+    let mut accounts: Vec<(Cid, &Account)> = accounts.collect();
+    accounts.sort_by_key(|(cid, _)| *cid);
+
+    let mut records = Vec::with_capacity(accounts.len());
+    for (cid, account) in accounts {
+        let mut record = AccountRecord {
+            client: cid,
+            available: account.available.round_dp(MAX_DECIMAL_PLACES).normalize(),
+            held: account.held.round_dp(MAX_DECIMAL_PLACES).normalize(),
+            total: account
+                .total()
+                .ok_or(Error::MathError)?
+                .round_dp(MAX_DECIMAL_PLACES)
+                .normalize(),
+            locked: account.locked,
+            owed: None,
+        };
+        if split_owed {
+            if record.available < Decimal::ZERO {
+                record.owed = Some(record.available.abs());
+                record.available = Decimal::ZERO;
+            } else {
+                record.owed = Some(Decimal::ZERO);
+            }
+        }
+        records.push(record);
+    }
+
+    Ok(records)
+}
+
+/// Represents the balances of a client's account, as written by [`write_transactions`].
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct AccountBalances {
+    /// The funds available.
+    pub available: Decimal,
+    /// The funds held for dispute.
+    pub held: Decimal,
+    /// The total funds, _i.e._, `available + held`.
+    pub total: Decimal,
+    /// Whether the account is locked.
+    pub locked: bool,
+}
+
+impl From<&AccountRecord> for AccountBalances {
+    fn from(record: &AccountRecord) -> Self {
+        Self {
+            available: record.available,
+            held: record.held,
+            total: record.total,
+            locked: record.locked,
+        }
+    }
+}
+
+/// Represents a client whose account differs from the expected one,
+/// as reported by [`compare_to_expected`].
+#[derive(Debug, PartialEq)]
+pub struct AccountDiff {
+    /// The client ID of the account.
+    pub client: Cid,
+    /// The expected balances, or `None` if the client was not expected.
+    pub expected: Option<AccountBalances>,
+    /// The actual balances, or `None` if the client has no account.
+    pub actual: Option<AccountBalances>,
+}
+
+/// Compares the accounts in `txs` against the expected accounts read from `expected`,
+/// _e.g._, the output of a reference implementation.
+///
+/// The expected accounts are read in the format written by [`write_transactions`],
+/// and compared against the balances that [`write_transactions`] would write for `txs`.
+/// Returns the clients whose `available`, `held`, `total` or `locked` differ,
+/// or that are present on only one side, sorted by client ID.
+///
+/// # Examples
+///
+/// ```
+/// use toy_payments_engine::*;
+/// use toy_payments_engine::csv::*;
+/// use rust_decimal_macros::dec;
+///
+/// let mut txs = Txs::new();
+/// txs.deposit(1, 1001, dec!(10)).unwrap();
+///
+/// let expected = "client,available,held,total,locked\n1,10,0,10,false\n";
+/// assert_eq!(compare_to_expected(&txs, expected.as_bytes()).unwrap(), vec![]);
+/// ```
+pub fn compare_to_expected<R: io::Read>(
+    txs: &Txs,
+    expected: R,
+) -> Result<Vec<AccountDiff>, Box<dyn error::Error>> {
+    let mut reader = ReaderBuilder::new().trim(Trim::All).from_reader(expected);
+    let mut expected = HashMap::new();
+    for result in reader.deserialize() {
+        let record: AccountRecord = result?;
+        if let Entry::Vacant(entry) = expected.entry(record.client) {
+            entry.insert(AccountBalances::from(&record));
+        } else {
+            return Err(format!("Duplicate expected account for client {}", record.client).into());
+        }
+    }
+
+    let mut diffs = Vec::new();
+    for record in account_records(txs.accounts(), false)? {
+        let actual = AccountBalances::from(&record);
+        match expected.remove(&record.client) {
+            Some(expected) if expected == actual => {}
+            expected => diffs.push(AccountDiff {
+                client: record.client,
+                expected,
+                actual: Some(actual),
+            }),
+        }
+    }
+    diffs.extend(expected.into_iter().map(|(client, expected)| AccountDiff {
+        client,
+        expected: Some(expected),
+        actual: None,
+    }));
+    diffs.sort_by_key(|diff| diff.client);
+
+    Ok(diffs)
+}
+
+/// Represents a transaction row annotated with its client's running balance,
+/// as written by [`write_ledger_with_balances_csv`].
+#[derive(Debug, Serialize)]
+struct LedgerRecord {
+    tx: Txid,
+    #[serde(rename = "type")]
+    kind: TxKind,
+    client: Cid,
+    amount: Option<Decimal>,
+    available_after: Decimal,
+}
+
+/// Writes the stored transactions in `txs` to a `Write`r `wtr`,
+/// annotating each one with the client's available balance right after it was applied.
+///
+/// Transactions are replayed in the order they were recorded by the engine,
+/// starting from zero balances.
+/// The columns written are `tx`, `type`, `client`, `amount` and `available_after`.
+///
+/// # Examples
+///
+/// ```
+/// use toy_payments_engine::*;
+/// use toy_payments_engine::csv::*;
+/// use rust_decimal_macros::dec;
+///
+/// let mut txs = Txs::new();
+/// let mut buf = vec![];
+///
+/// txs.deposit(1, 1001, dec!(10)).unwrap();
+/// txs.withdrawal(1, 1002, dec!(4)).unwrap();
+///
+/// write_ledger_with_balances_csv(&txs, &mut buf).unwrap();
+///
+/// assert_eq!(
+///     std::str::from_utf8(&buf).unwrap(),
+///     "tx,type,client,amount,available_after
+/// 1001,deposit,1,10,10
+/// 1002,withdrawal,1,4,6
+/// "
+/// );
+/// ```
+pub fn write_ledger_with_balances_csv<W: io::Write>(
+    txs: &Txs,
+    wtr: W,
+) -> Result<(), Box<dyn error::Error>> {
+    let mut writer = WriterBuilder::new().has_headers(false).from_writer(wtr);
+
+    writer.write_record(["tx", "type", "client", "amount", "available_after"])?;
+
+    let mut ledger: Vec<&Tx> = txs.txs.values().collect();
+    ledger.sort_by_key(|tx| tx.seq);
+
+    let mut balances: HashMap<Cid, Decimal> = HashMap::new();
+    for tx in ledger {
+        let available = balances.entry(tx.cid).or_default();
+        let amount = tx.amount.unwrap_or_default();
+        let available_after = match tx.kind {
+            TxKind::Deposit => available.checked_add(amount),
+            TxKind::Withdrawal => available.checked_sub(amount),
+            _ => Some(*available),
+        }
+        .ok_or_else(|| format!("Running balance overflow in tx {}", tx.txid))?;
+        *available = available_after;
+
+        writer.serialize(LedgerRecord {
+            tx: tx.txid,
+            kind: tx.kind,
+            client: tx.cid,
+            amount: tx.amount,
+            available_after,
+        })?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Represents the net movements of a client, as written by [`write_net_ledger_csv`].
+#[derive(Debug, Default, Serialize)]
+struct NetLedgerRecord {
+    client: Cid,
+    deposited: Decimal,
+    withdrawn: Decimal,
+    net: Decimal,
+}
+
+/// Writes the net movements of every client in `txs` to a `Write`r `wtr`.
+///
+/// The stored deposits and withdrawals of each client are folded into a single row
+/// with the columns `client`, `deposited`, `withdrawn` and `net`.
+/// Rows are sorted by client ID.
+///
+/// # Examples
+///
+/// ```
+/// use toy_payments_engine::*;
+/// use toy_payments_engine::csv::*;
+/// use rust_decimal_macros::dec;
+///
+/// let mut txs = Txs::new();
+/// let mut buf = vec![];
+///
+/// txs.deposit(1, 1001, dec!(10)).unwrap();
+/// txs.deposit(1, 1002, dec!(5)).unwrap();
+/// txs.withdrawal(1, 1003, dec!(3)).unwrap();
+///
+/// write_net_ledger_csv(&txs, &mut buf).unwrap();
+///
+/// assert_eq!(
+///     std::str::from_utf8(&buf).unwrap(),
+///     "client,deposited,withdrawn,net
+/// 1,15,3,12
+/// "
+/// );
+/// ```
+pub fn write_net_ledger_csv<W: io::Write>(txs: &Txs, wtr: W) -> Result<(), Box<dyn error::Error>> {
+    let mut writer = WriterBuilder::new().has_headers(false).from_writer(wtr);
+
+    writer.write_record(["client", "deposited", "withdrawn", "net"])?;
+
+    let mut records: HashMap<Cid, NetLedgerRecord> = HashMap::new();
+    for tx in txs.txs.values() {
+        let record = records.entry(tx.cid).or_insert_with(|| NetLedgerRecord {
+            client: tx.cid,
+            ..Default::default()
+        });
+        let amount = tx.amount.unwrap_or_default();
+        match tx.kind {
+            TxKind::Deposit => {
+                record.deposited = record
+                    .deposited
+                    .checked_add(amount)
+                    .ok_or(Error::MathError)?
+            }
+            TxKind::Withdrawal => {
+                record.withdrawn = record
+                    .withdrawn
+                    .checked_add(amount)
+                    .ok_or(Error::MathError)?
+            }
+            _ => {}
+        }
+    }
+
+    let mut records: Vec<NetLedgerRecord> = records.into_values().collect();
+    records.sort_by_key(|record| record.client);
+
+    for mut record in records {
+        record.net = record
+            .deposited
+            .checked_sub(record.withdrawn)
+            .ok_or(Error::MathError)?
+            .round_dp(MAX_DECIMAL_PLACES)
+            .normalize();
+        record.deposited = record.deposited.round_dp(MAX_DECIMAL_PLACES).normalize();
+        record.withdrawn = record.withdrawn.round_dp(MAX_DECIMAL_PLACES).normalize();
+        writer.serialize(record)?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Represents a disputed transaction row, as written by [`write_disputes_csv`].
+#[derive(Debug, Serialize)]
+struct DisputeRecord {
+    tx: Txid,
+    client: Cid,
+    amount: Option<Decimal>,
+    state: DisputeState,
+}
+
+/// Writes every transaction in `txs` that is currently or was previously disputed
+/// to a `Write`r `wtr`.
+///
+/// The columns written are `tx`, `client`, `amount` and `state`,
+/// where `state` is one of `disputed`, `resolved` or `chargedback`.
+/// Rows are sorted by transaction ID.
+///
+/// # Examples
+///
+/// ```
+/// use toy_payments_engine::*;
+/// use toy_payments_engine::csv::*;
+/// use rust_decimal_macros::dec;
+///
+/// let mut txs = Txs::new();
+/// let mut buf = vec![];
+///
+/// txs.deposit(1, 1001, dec!(10)).unwrap();
+/// txs.deposit(1, 1002, dec!(4)).unwrap();
+/// txs.dispute(1, 1002).unwrap();
+///
+/// write_disputes_csv(&txs, &mut buf).unwrap();
+///
+/// assert_eq!(
+///     std::str::from_utf8(&buf).unwrap(),
+///     "tx,client,amount,state
+/// 1002,1,4,disputed
+/// "
+/// );
+/// ```
+pub fn write_disputes_csv<W: io::Write>(txs: &Txs, wtr: W) -> Result<(), Box<dyn error::Error>> {
+    let mut writer = WriterBuilder::new().has_headers(false).from_writer(wtr);
+
+    writer.write_record(["tx", "client", "amount", "state"])?;
+
+    let mut disputes: Vec<&Tx> = txs
+        .txs
+        .values()
+        .filter(|tx| tx.state != DisputeState::Undisputed)
+        .collect();
+    disputes.sort_by_key(|tx| tx.txid);
+
+    for tx in disputes {
+        writer.serialize(DisputeRecord {
+            tx: tx.txid,
+            client: tx.cid,
+            amount: tx.amount,
+            state: tx.state,
+        })?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes the transactions `txs` to a `Write`r `wtr` in the same CSV format
+/// read by [`process_transactions`], _e.g._, to re-emit a filtered transaction stream.
+///
+/// The first row is the [`INPUT_HEADER`] row.
+/// Transactions are written in the given order,
+/// with an empty `amount` for disputes, resolves, chargebacks and freezes.
+///
+/// # Examples
+///
+/// ```
+/// use toy_payments_engine::*;
+/// use toy_payments_engine::csv::*;
+/// use rust_decimal_macros::dec;
+///
+/// let mut buf = vec![];
+/// let txs = [Tx::deposit(1, 1001, dec!(2.5)), Tx::dispute(1, 1001)];
+///
+/// write_raw_transactions(&txs, &mut buf).unwrap();
+///
+/// assert_eq!(
+///     std::str::from_utf8(&buf).unwrap(),
+///     "type,client,tx,amount
+/// deposit,1,1001,2.5
+/// dispute,1,1001,
+/// "
+/// );
+/// ```
+pub fn write_raw_transactions<W: io::Write>(
+    txs: &[Tx],
+    wtr: W,
+) -> Result<(), Box<dyn error::Error>> {
+    let mut writer = WriterBuilder::new().has_headers(false).from_writer(wtr);
+
+    writer.write_record(INPUT_HEADER)?;
+    for tx in txs {
+        writer.serialize(tx)?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::{collections::HashMap, io::BufWriter};
+
+    use rust_decimal::Decimal;
     use rust_decimal_macros::dec;
 
-    use crate::{Account, Txs};
+    use crate::{Account, Error, IdField, Tx, TxKind, Txs};
 
-    use super::{process_transactions, write_transactions};
+    use super::{
+        compare_to_expected, import_accounts, process_to_map, process_transactions,
+        process_transactions_many, process_transactions_parallel, process_transactions_persisting,
+        process_transactions_with, process_transactions_with_progress, write_changed_transactions,
+        write_disputes_csv, write_ledger_with_balances_csv, write_net_ledger_csv,
+        write_raw_transactions, write_transactions, write_transactions_grouped,
+        write_transactions_tee, write_transactions_with, AccountBalances, AccountDiff, BoolFormat,
+        CsvError, LineError, QuoteStyle, ReadOptions, ReaderBuilder, Trim, TxRecord, WriteOptions,
+        OUTPUT_HEADER,
+    };
 
     #[test]
     fn test_process_transactions_with_errors() {
@@ -126,7 +1473,7 @@ dispute, 2, 2
 chargeback, 2, 2
 ";
 
-        let txs = process_transactions(data.as_bytes()).unwrap();
+        let (txs, report) = process_transactions(data.as_bytes()).unwrap();
         assert_eq!(
             txs.accounts.get(&1).unwrap(),
             &Account::new(dec!(1.5), dec!(0), false)
@@ -135,6 +1482,41 @@ chargeback, 2, 2
             txs.accounts.get(&2).unwrap(),
             &Account::new(dec!(-3), dec!(0), true)
         );
+        assert_eq!(
+            report.errors,
+            vec![
+                LineError {
+                    line: 8,
+                    error: Error::TxAlreadyDisputed,
+                    raw: "dispute, 1, 1".to_string(),
+                },
+                LineError {
+                    line: 10,
+                    error: Error::TxNotDisputed,
+                    raw: "resolve, 1, 1".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_process_transactions_with_crlf() {
+        let data = "type, client, tx, amount\r\n\
+deposit, 1, 1, 1.0\r\n\
+deposit, 2, 2, 2.5\r\n\
+withdrawal, 1, 3, 0.25\r\n\
+dispute, 2, 2\r\n";
+
+        let (txs, _) = process_transactions(data.as_bytes()).unwrap();
+        assert_eq!(
+            txs.accounts.get(&1).unwrap(),
+            &Account::new(dec!(0.75), dec!(0), false)
+        );
+        assert_eq!(
+            txs.accounts.get(&2).unwrap(),
+            &Account::new(dec!(0), dec!(2.5), false)
+        );
+        assert_eq!(txs.txs.get(&1).unwrap().amount, Some(dec!(1.0)));
     }
 
     #[test]
@@ -149,4 +1531,1018 @@ chargeback, 2, 2
             "client,available,held,total,locked\n"
         );
     }
+
+    #[test]
+    fn test_write_transactions_format() {
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, dec!(10.5)).unwrap();
+        txs.deposit(1, 1002, dec!(0.0025)).unwrap();
+        txs.withdrawal(1, 1003, dec!(2)).unwrap();
+        txs.dispute(1, 1002).unwrap();
+        txs.charge_back(1, 1002).unwrap();
+        let mut buf = vec![];
+
+        write_transactions(&txs, BufWriter::new(&mut buf)).unwrap();
+
+        assert_eq!(
+            std::str::from_utf8(&buf).unwrap(),
+            "client,available,held,total,locked\n1,8.5,0,8.5,true\n"
+        );
+    }
+
+    #[test]
+    fn test_write_transactions_without_phantom_accounts() {
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        assert_eq!(txs.dispute(2, 1002), Err(Error::TxNotFound));
+        assert_eq!(txs.resolve(3, 1003), Err(Error::TxNotFound));
+        assert_eq!(txs.charge_back(4, 1004), Err(Error::TxNotFound));
+        assert_eq!(
+            txs.withdrawal(5, 1005, dec!(1)),
+            Err(Error::InsuffienctFunds)
+        );
+        assert_eq!(txs.deposit(6, 1001, dec!(1)), Err(Error::TxAlreadyExists));
+        let mut buf = vec![];
+
+        write_transactions(&txs, BufWriter::new(&mut buf)).unwrap();
+
+        assert_eq!(
+            std::str::from_utf8(&buf).unwrap(),
+            "client,available,held,total,locked\n1,10,0,10,false\n"
+        );
+    }
+
+    #[test]
+    fn test_write_transactions_skip_empty() {
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.withdrawal(1, 1002, dec!(10)).unwrap();
+        txs.deposit(2, 1003, dec!(5)).unwrap();
+        txs.dispute(2, 1003).unwrap();
+        txs.charge_back(2, 1003).unwrap();
+        txs.deposit(3, 1004, dec!(1)).unwrap();
+
+        let options = WriteOptions {
+            skip_empty: true,
+            ..Default::default()
+        };
+        let mut buf = vec![];
+        write_transactions_with(&txs, BufWriter::new(&mut buf), &options).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&buf).unwrap(),
+            "client,available,held,total,locked\n2,0,0,0,true\n3,1,0,1,false\n"
+        );
+
+        let mut buf = vec![];
+        write_transactions(&txs, BufWriter::new(&mut buf)).unwrap();
+        assert!(std::str::from_utf8(&buf)
+            .unwrap()
+            .contains("\n1,0,0,0,false\n"));
+    }
+
+    #[test]
+    fn test_write_transactions_sorted_by_client() {
+        let mut txs = Txs::new();
+        txs.deposit(3, 1001, dec!(3)).unwrap();
+        txs.deposit(1, 1002, dec!(1)).unwrap();
+        txs.deposit(4, 1003, dec!(4)).unwrap();
+        txs.deposit(2, 1004, dec!(2)).unwrap();
+        let mut buf = vec![];
+
+        write_transactions(&txs, BufWriter::new(&mut buf)).unwrap();
+
+        assert_eq!(
+            std::str::from_utf8(&buf).unwrap(),
+            "\
+client,available,held,total,locked
+1,1,0,1,false
+2,2,0,2,false
+3,3,0,3,false
+4,4,0,4,false
+"
+        );
+    }
+
+    #[test]
+    fn test_write_transactions_rounded() {
+        let mut txs = Txs::new();
+        txs.accounts
+            .insert(1, Account::new(dec!(5.12345), dec!(1.00005), false));
+        txs.accounts
+            .insert(2, Account::new(dec!(10.05), dec!(0), false));
+        let mut buf = vec![];
+
+        write_transactions(&txs, BufWriter::new(&mut buf)).unwrap();
+
+        assert_eq!(
+            std::str::from_utf8(&buf).unwrap(),
+            "\
+client,available,held,total,locked
+1,5.1234,1,6.1235,false
+2,10.05,0,10.05,false
+"
+        );
+    }
+
+    #[test]
+    fn test_write_transactions_total_overflow() {
+        let mut txs = Txs::new();
+        txs.accounts
+            .insert(1, Account::new(Decimal::MAX, dec!(1), false));
+        let mut buf = vec![];
+
+        let err = write_transactions(&txs, BufWriter::new(&mut buf)).unwrap_err();
+        assert_eq!(err.downcast_ref::<Error>(), Some(&Error::MathError));
+    }
+
+    #[test]
+    fn test_write_transactions_quote_style_always() {
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, dec!(2.5)).unwrap();
+        let mut buf = vec![];
+
+        let options = WriteOptions {
+            quote_style: QuoteStyle::Always,
+            ..Default::default()
+        };
+        write_transactions_with(&txs, BufWriter::new(&mut buf), &options).unwrap();
+
+        assert_eq!(
+            std::str::from_utf8(&buf).unwrap(),
+            "\"client\",\"available\",\"held\",\"total\",\"locked\"\n\
+             \"1\",\"2.5\",\"0\",\"2.5\",\"false\"\n"
+        );
+    }
+
+    #[test]
+    fn test_write_transactions_output_header() {
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, dec!(1)).unwrap();
+        let mut buf = vec![];
+
+        write_transactions(&txs, BufWriter::new(&mut buf)).unwrap();
+
+        let output = std::str::from_utf8(&buf).unwrap();
+        assert_eq!(
+            output.lines().next(),
+            Some(OUTPUT_HEADER.join(",").as_str())
+        );
+    }
+
+    #[test]
+    fn test_write_transactions_split_owed() {
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.withdrawal(1, 1002, dec!(8)).unwrap();
+        txs.dispute(1, 1001).unwrap();
+        let mut buf = vec![];
+
+        let options = WriteOptions {
+            split_owed: true,
+            ..Default::default()
+        };
+        write_transactions_with(&txs, BufWriter::new(&mut buf), &options).unwrap();
+
+        assert_eq!(
+            std::str::from_utf8(&buf).unwrap(),
+            "client,available,held,total,locked,owed\n1,0,10,2,false,8\n"
+        );
+    }
+
+    #[test]
+    fn test_import_accounts() {
+        let data = "\
+client, available, held, locked
+1, 10.5, 0, false
+2, 0, 3.25, false
+3, 7, 1, true
+";
+
+        let txs = import_accounts(data.as_bytes()).unwrap();
+        assert_eq!(txs.accounts.len(), 3);
+        assert_eq!(
+            txs.accounts.get(&1).unwrap(),
+            &Account::new(dec!(10.5), dec!(0), false)
+        );
+        assert_eq!(
+            txs.accounts.get(&2).unwrap(),
+            &Account::new(dec!(0), dec!(3.25), false)
+        );
+        assert_eq!(
+            txs.accounts.get(&3).unwrap(),
+            &Account::new(dec!(7), dec!(1), true)
+        );
+    }
+
+    #[test]
+    fn test_import_accounts_with_errors() {
+        let negative = "client, available, held, locked\n1, -1, 0, false\n";
+        assert!(import_accounts(negative.as_bytes()).is_err());
+
+        let duplicate = "client, available, held, locked\n1, 1, 0, false\n1, 2, 0, false\n";
+        assert!(import_accounts(duplicate.as_bytes()).is_err());
+
+        let unparseable = "client, available, held, locked\n1, abc, 0, false\n";
+        assert!(import_accounts(unparseable.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_tx_round_trip() {
+        let data = "type, client, tx, amount\ndeposit, 1, 1001, 1.5\ndispute, 1, 1001,\n";
+        let mut reader = ReaderBuilder::new()
+            .trim(Trim::All)
+            .flexible(true)
+            .from_reader(data.as_bytes());
+        let parsed: Vec<Tx> = reader
+            .deserialize::<TxRecord>()
+            .map(|record| record.unwrap().into_tx().unwrap())
+            .collect();
+        assert_eq!(
+            parsed,
+            vec![Tx::deposit(1, 1001, dec!(1.5)), Tx::dispute(1, 1001)]
+        );
+        assert_ne!(parsed[0], Tx::deposit(1, 1001, dec!(1.25)));
+
+        let (txs, _) = process_transactions(data.as_bytes()).unwrap();
+        assert_eq!(txs.txs[&1001], Tx::deposit(1, 1001, dec!(1.5)));
+    }
+
+    #[test]
+    fn test_tx_from_str() {
+        assert_eq!(
+            "deposit, 1, 1001, 5.0".parse(),
+            Ok(Tx::deposit(1, 1001, dec!(5.0)))
+        );
+        assert_eq!(
+            "withdrawal,2,1002,1.5".parse(),
+            Ok(Tx::withdrawal(2, 1002, dec!(1.5)))
+        );
+        assert_eq!("dispute, 1, 1001".parse(), Ok(Tx::dispute(1, 1001)));
+        assert_eq!("resolve, 1, 1001,".parse(), Ok(Tx::resolve(1, 1001)));
+        assert_eq!(
+            " chargeback, 1, 1001, ".parse(),
+            Ok(Tx::charge_back(1, 1001))
+        );
+        assert_eq!(
+            "correction, 1, 1001, 4.0".parse(),
+            Ok(Tx::correction(1, 1001, dec!(4.0)))
+        );
+
+        for line in [
+            "",
+            "deposit, 1",
+            "deposit, 1, 1001, abc",
+            "refund, 1, 1001, 5.0",
+            "deposit, 1, 1001, 5.0, 1",
+            "deposit, 1, 1001, 5.0\ndeposit, 1, 1002, 5.0",
+        ] {
+            assert_eq!(line.parse::<Tx>(), Err(Error::InvalidTx), "{:?}", line);
+        }
+        assert_eq!(
+            "deposit, 70000, 1001, 5.0".parse::<Tx>(),
+            Err(Error::IdOutOfRange(IdField::Client))
+        );
+    }
+
+    #[test]
+    fn test_id_out_of_range() {
+        let record = TxRecord {
+            kind: TxKind::Deposit,
+            client: 70000,
+            tx: 1,
+            amount: Some(dec!(1)),
+        };
+        assert_eq!(
+            record.into_tx().unwrap_err(),
+            Error::IdOutOfRange(IdField::Client)
+        );
+
+        let record = TxRecord {
+            kind: TxKind::Deposit,
+            client: 1,
+            tx: 5000000000,
+            amount: Some(dec!(1)),
+        };
+        assert_eq!(
+            record.into_tx().unwrap_err(),
+            Error::IdOutOfRange(IdField::Tx)
+        );
+    }
+
+    #[test]
+    fn test_process_transactions_with_ids_out_of_range() {
+        let data = "\
+type, client, tx, amount
+deposit, 70000, 1, 1.0
+deposit, 1, 5000000000, 2.0
+deposit, 1, 3, 4.0
+";
+
+        let (txs, report) = process_transactions(data.as_bytes()).unwrap();
+        assert_eq!(txs.accounts.len(), 1);
+        assert_eq!(
+            report.errors,
+            vec![
+                LineError {
+                    line: 2,
+                    error: Error::IdOutOfRange(IdField::Client),
+                    raw: "deposit, 70000, 1, 1.0".to_string(),
+                },
+                LineError {
+                    line: 3,
+                    error: Error::IdOutOfRange(IdField::Tx),
+                    raw: "deposit, 1, 5000000000, 2.0".to_string(),
+                },
+            ]
+        );
+        assert_eq!(
+            txs.accounts.get(&1).unwrap(),
+            &Account::new(dec!(4), dec!(0), false)
+        );
+    }
+
+    #[test]
+    fn test_write_ledger_with_balances() {
+        let mut txs = Txs::new();
+        txs.deposit(2, 7, dec!(5)).unwrap();
+        txs.deposit(1, 3, dec!(2.5)).unwrap();
+        txs.deposit(2, 1, dec!(1.25)).unwrap();
+        let mut buf = vec![];
+
+        write_ledger_with_balances_csv(&txs, &mut buf).unwrap();
+
+        assert_eq!(
+            std::str::from_utf8(&buf).unwrap(),
+            "\
+tx,type,client,amount,available_after
+7,deposit,2,5,5
+3,deposit,1,2.5,2.5
+1,deposit,2,1.25,6.25
+"
+        );
+    }
+
+    #[test]
+    fn test_write_disputes() {
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.deposit(2, 1002, dec!(20)).unwrap();
+        txs.deposit(1, 1003, dec!(30)).unwrap();
+        txs.dispute(2, 1002).unwrap();
+        txs.dispute(1, 1001).unwrap();
+        txs.resolve(1, 1001).unwrap();
+        let mut buf = vec![];
+
+        write_disputes_csv(&txs, &mut buf).unwrap();
+
+        assert_eq!(
+            std::str::from_utf8(&buf).unwrap(),
+            "\
+tx,client,amount,state
+1001,1,10,resolved
+1002,2,20,disputed
+"
+        );
+    }
+
+    #[test]
+    fn test_write_changed_transactions() {
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.deposit(2, 1002, dec!(20)).unwrap();
+        txs.deposit(3, 1003, dec!(30)).unwrap();
+        let snapshot = txs.engine_snapshot();
+
+        txs.deposit(2, 1004, dec!(5)).unwrap();
+        let mut buf = vec![];
+        write_changed_transactions(&txs, &snapshot, &mut buf).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&buf).unwrap(),
+            "client,available,held,total,locked\n2,25,0,25,false\n"
+        );
+
+        let snapshot = txs.engine_snapshot();
+        txs.deposit(4, 1005, dec!(1)).unwrap();
+        let mut buf = vec![];
+        write_changed_transactions(&txs, &snapshot, &mut buf).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&buf).unwrap(),
+            "client,available,held,total,locked\n4,1,0,1,false\n"
+        );
+    }
+
+    #[test]
+    fn test_process_to_map() {
+        let data = include_str!("../input-example.csv");
+
+        let accounts = process_to_map(data.as_bytes()).unwrap();
+        assert_eq!(
+            accounts,
+            HashMap::from([
+                (1, Account::new(dec!(0.5), dec!(0), true)),
+                (2, Account::new(dec!(2), dec!(0), false)),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_write_net_ledger() {
+        let mut txs = Txs::new();
+        txs.deposit(2, 1001, dec!(10)).unwrap();
+        txs.deposit(1, 1002, dec!(20.5)).unwrap();
+        txs.withdrawal(2, 1003, dec!(4)).unwrap();
+        txs.deposit(2, 1004, dec!(7.25)).unwrap();
+        txs.withdrawal(2, 1005, dec!(1.25)).unwrap();
+        txs.dispute(1, 1002).unwrap();
+        let mut buf = vec![];
+
+        write_net_ledger_csv(&txs, &mut buf).unwrap();
+
+        assert_eq!(
+            std::str::from_utf8(&buf).unwrap(),
+            "\
+client,deposited,withdrawn,net
+1,20.5,0,20.5
+2,17.25,5.25,12
+"
+        );
+    }
+
+    #[test]
+    fn test_process_transactions_many() {
+        let first = "\
+type, client, tx, amount
+deposit, 1, 1, 10.0
+deposit, 2, 2, 4.0
+withdrawal, 1, 3, 2.5
+";
+        let second = "\
+type, client, tx, amount
+dispute, 1, 1,
+deposit, 2, 3, 1.0
+chargeback, 1, 1,
+withdrawal, 2, 4, 1.0
+";
+        let (txs, report) =
+            process_transactions_many([first.as_bytes(), second.as_bytes()]).unwrap();
+        let concatenated = format!(
+            "{}{}",
+            first,
+            second.lines().skip(1).collect::<Vec<_>>().join("\n")
+        );
+        let (expected, _) = process_transactions(concatenated.as_bytes()).unwrap();
+
+        assert_eq!(txs.accounts, expected.accounts);
+        assert_eq!(txs.txs.len(), expected.txs.len());
+        assert_eq!(report.processed, 7);
+        assert_eq!(
+            report.errors,
+            vec![LineError {
+                line: 3,
+                error: Error::TxAlreadyExists,
+                raw: "deposit, 2, 3, 1.0".to_string(),
+            }]
+        );
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(-2.5), dec!(0), true)));
+    }
+
+    #[test]
+    fn test_process_transactions_quoted_raw() {
+        let data = "\
+type,client,tx,amount
+deposit,1,1,\"1.0\"
+\"withdrawal\",1,2,\"x\"
+";
+        let (_, report) = process_transactions(data.as_bytes()).unwrap();
+
+        assert_eq!(
+            report.errors,
+            vec![LineError {
+                line: 3,
+                error: Error::InvalidTx,
+                raw: "withdrawal,1,2,x".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_write_transactions_tee() {
+        let mut txs = Txs::new();
+        txs.deposit(2, 1001, dec!(3)).unwrap();
+        txs.deposit(1, 1002, dec!(1.5)).unwrap();
+        txs.dispute(2, 1001).unwrap();
+
+        let (mut first, mut second) = (Vec::new(), Vec::new());
+        write_transactions_tee(&txs, &mut [&mut first, &mut second]).unwrap();
+
+        let mut expected = Vec::new();
+        write_transactions(&txs, &mut expected).unwrap();
+        assert_eq!(first, expected);
+        assert_eq!(second, expected);
+        assert_eq!(
+            String::from_utf8(first).unwrap(),
+            "client,available,held,total,locked\n1,1.5,0,1.5,false\n2,0,3,3,false\n"
+        );
+    }
+
+    #[test]
+    fn test_compare_to_expected() {
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.deposit(2, 1002, dec!(3)).unwrap();
+        txs.dispute(2, 1002).unwrap();
+        txs.charge_back(2, 1002).unwrap();
+        txs.deposit(3, 1003, dec!(1)).unwrap();
+
+        let mut matching = Vec::new();
+        write_transactions(&txs, &mut matching).unwrap();
+        assert_eq!(
+            compare_to_expected(&txs, matching.as_slice()).unwrap(),
+            vec![]
+        );
+
+        let mismatching = "\
+client, available, held, total, locked
+1, 10.0000, 0, 10, false
+2, 0, 0, 0, false
+4, 1, 0, 1, false
+";
+        let balances = |available, held, total, locked| AccountBalances {
+            available,
+            held,
+            total,
+            locked,
+        };
+        assert_eq!(
+            compare_to_expected(&txs, mismatching.as_bytes()).unwrap(),
+            vec![
+                AccountDiff {
+                    client: 2,
+                    expected: Some(balances(dec!(0), dec!(0), dec!(0), false)),
+                    actual: Some(balances(dec!(0), dec!(0), dec!(0), true)),
+                },
+                AccountDiff {
+                    client: 3,
+                    expected: None,
+                    actual: Some(balances(dec!(1), dec!(0), dec!(1), false)),
+                },
+                AccountDiff {
+                    client: 4,
+                    expected: Some(balances(dec!(1), dec!(0), dec!(1), false)),
+                    actual: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_write_transactions_bool_format() {
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, dec!(2)).unwrap();
+        txs.deposit(2, 1002, dec!(3)).unwrap();
+        txs.dispute(2, 1002).unwrap();
+        txs.charge_back(2, 1002).unwrap();
+
+        for (bool_format, unlocked, locked) in [
+            (BoolFormat::TrueFalse, "false", "true"),
+            (BoolFormat::OneZero, "0", "1"),
+            (BoolFormat::YesNo, "no", "yes"),
+        ] {
+            let options = WriteOptions {
+                bool_format,
+                ..Default::default()
+            };
+            let mut buf = Vec::new();
+            write_transactions_with(&txs, &mut buf, &options).unwrap();
+            assert_eq!(
+                String::from_utf8(buf).unwrap(),
+                format!(
+                    "client,available,held,total,locked\n1,2,0,2,{}\n2,0,0,0,{}\n",
+                    unlocked, locked
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn test_process_transactions_persisting() {
+        let data = "\
+type, client, tx, amount
+deposit, 1, 1, 10.0
+deposit, 2, 2, 2.0
+withdrawal, 1, 3, 1.5
+withdrawal, 2, 4, 3.0
+dispute, 2, 2,
+chargeback, 2, 2,
+deposit, 2, 5, 1.0
+";
+        let mut persisted = Vec::new();
+        let mut store = HashMap::new();
+        let (txs, report) = process_transactions_persisting(data.as_bytes(), |cid, account| {
+            persisted.push(cid);
+            store.insert(
+                cid,
+                Account::new(account.available, account.held, account.locked),
+            );
+        })
+        .unwrap();
+
+        assert_eq!(report.errors.len(), 2);
+        assert_eq!(persisted, vec![1, 2, 1, 2, 2]);
+        assert_eq!(store, txs.accounts);
+        assert_eq!(store[&2], Account::new(dec!(0), dec!(0), true));
+    }
+
+    #[test]
+    fn test_process_correction() {
+        let data = "\
+type, client, tx, amount
+deposit, 1, 1, 10.0
+correction, 1, 1, 7.5
+correction, 1, 2, 1.0
+";
+        let (txs, report) = process_transactions(data.as_bytes()).unwrap();
+        assert_eq!(
+            report.errors,
+            vec![LineError {
+                line: 4,
+                error: Error::TxNotFound,
+                raw: "correction, 1, 2, 1.0".to_string(),
+            }]
+        );
+        assert_eq!(txs.accounts[&1], Account::new(dec!(7.5), dec!(0), false));
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_process_transactions_gz() {
+        use std::io::Write;
+
+        use flate2::{write::GzEncoder, Compression};
+
+        use super::process_transactions_gz;
+
+        let data = "\
+type, client, tx, amount
+deposit, 1, 1, 10.0
+deposit, 2, 2, 4.0
+withdrawal, 1, 3, 2.5
+dispute, 2, 2,
+withdrawal, 2, 4, 1.0
+";
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data.as_bytes()).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let (txs, report) = process_transactions_gz(&compressed[..]).unwrap();
+        let (expected, expected_report) = process_transactions(data.as_bytes()).unwrap();
+        assert_eq!(txs.accounts, expected.accounts);
+        assert_eq!(report, expected_report);
+        assert_eq!(txs.accounts[&1], Account::new(dec!(7.5), dec!(0), false));
+
+        assert!(process_transactions_gz(data.as_bytes()).is_err());
+    }
+
+    #[cfg(feature = "zip")]
+    #[test]
+    fn test_process_transactions_zip() {
+        use std::io::{Cursor, Write};
+
+        use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+        use super::process_transactions_zip;
+
+        let mut archive = ZipWriter::new(Cursor::new(Vec::new()));
+        let entries = [
+            (
+                "2-afternoon.csv",
+                "type, client, tx, amount\ndispute, 1, 1,\ndeposit, 2, 2, 9.0\n",
+            ),
+            ("README.txt", "not a csv file"),
+            (
+                "1-morning.csv",
+                "type, client, tx, amount\ndeposit, 1, 1, 10.0\ndeposit, 2, 2, 3.0\n",
+            ),
+        ];
+        for (name, contents) in entries {
+            let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+            archive.start_file(name, options).unwrap();
+            archive.write_all(contents.as_bytes()).unwrap();
+        }
+        let archive = archive.finish().unwrap();
+
+        let (txs, report) = process_transactions_zip(Cursor::new(archive.into_inner())).unwrap();
+        assert_eq!(report.processed, 4);
+        assert_eq!(
+            report.errors,
+            vec![LineError {
+                line: 3,
+                error: Error::TxAlreadyExists,
+                raw: "deposit, 2, 2, 9.0".to_string(),
+            }]
+        );
+        assert_eq!(txs.accounts[&1], Account::new(dec!(0), dec!(10), false));
+        assert_eq!(txs.accounts[&2], Account::new(dec!(3), dec!(0), false));
+    }
+
+    #[test]
+    fn test_write_transactions_grouped() {
+        let mut txs = Txs::new();
+        for cid in [4, 2, 5, 1, 3] {
+            txs.deposit(cid, cid.into(), dec!(1)).unwrap();
+        }
+        txs.freeze(4).unwrap();
+        txs.dispute(2, 2).unwrap();
+        txs.charge_back(2, 2).unwrap();
+
+        let mut buf = Vec::new();
+        write_transactions_grouped(&txs, &mut buf).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "client,available,held,total,locked
+1,1,0,1,false
+3,1,0,1,false
+5,1,0,1,false
+2,0,0,0,true
+4,1,0,1,true
+"
+        );
+    }
+
+    #[test]
+    fn test_csv_error_line() {
+        let data = b"\
+type, client, tx, amount
+deposit, 1, 1, 1.0
+deposit, 1, 2, \xff
+deposit, 1, 3, 2.0
+";
+        let err = process_transactions(&data[..]).unwrap_err();
+        let err = err.downcast_ref::<CsvError>().unwrap();
+        assert_eq!(err.line, 3);
+        assert!(err.to_string().starts_with("Error in line 3: "));
+    }
+
+    #[test]
+    fn test_process_transactions_error_raw_line() {
+        let data = "type,client,tx,amount\r\ndeposit,1,1,1.0\r\nwithdrawal,  1, 2,  5.0 \r\n";
+        let (_, report) = process_transactions(data.as_bytes()).unwrap();
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.errors[0].line, 3);
+        assert_eq!(report.errors[0].raw, "withdrawal,  1, 2,  5.0 ");
+    }
+
+    #[test]
+    fn test_process_transactions_error_line_counts_header() {
+        let data = "\
+type, client, tx, amount
+deposit, 1, 1, 1.0
+withdrawal, 1, 2, 5.0
+";
+        let (_, report) = process_transactions(data.as_bytes()).unwrap();
+        assert_eq!(
+            report.errors,
+            vec![LineError {
+                line: 3,
+                error: Error::InsuffienctFunds,
+                raw: "withdrawal, 1, 2, 5.0".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_process_transactions_without_headers() {
+        let data = "\
+deposit, 1, 1, 10.0
+deposit, 2, 2, 4.0
+withdrawal, 1, 3, 2.5
+dispute, 2, 2,
+withdrawal, 2, 4, 1.0
+";
+        let options = ReadOptions {
+            has_headers: false,
+            ..ReadOptions::default()
+        };
+        let (txs, report) = process_transactions_with(data.as_bytes(), &options).unwrap();
+        assert_eq!(report.processed, 5);
+        assert_eq!(
+            report.errors,
+            vec![LineError {
+                line: 5,
+                error: Error::InsuffienctFunds,
+                raw: "withdrawal, 2, 4, 1.0".to_string(),
+            }]
+        );
+        assert_eq!(txs.accounts[&1], Account::new(dec!(7.5), dec!(0), false));
+        assert_eq!(txs.accounts[&2], Account::new(dec!(0), dec!(4), false));
+
+        let (txs, report) = process_transactions(data.as_bytes()).unwrap();
+        assert_eq!(report.processed, 4);
+        assert!(report
+            .errors
+            .iter()
+            .all(|error| error.error == Error::InvalidTx));
+        assert!(txs.accounts.is_empty());
+    }
+
+    #[test]
+    fn test_process_transactions_with_delimiter() {
+        let data = "\
+type, client, tx, amount
+deposit, 1, 1, 10.0
+deposit, 2, 2, 4.0
+withdrawal, 1, 3, 2.5
+dispute, 2, 2,
+withdrawal, 2, 4, 1.0
+chargeback, 2, 2,
+";
+        let options = ReadOptions {
+            delimiter: b';',
+            ..ReadOptions::default()
+        };
+        let (txs, report) =
+            process_transactions_with(data.replace(',', ";").as_bytes(), &options).unwrap();
+        let (expected, expected_report) = process_transactions(data.as_bytes()).unwrap();
+
+        assert_eq!(txs.accounts, expected.accounts);
+        assert_eq!(report.processed, expected_report.processed);
+        assert_eq!(
+            report.errors,
+            vec![LineError {
+                line: 6,
+                error: Error::InsuffienctFunds,
+                raw: "withdrawal; 2; 4; 1.0".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_process_transactions_with_bom() {
+        let data = "\
+type, client, tx, amount
+deposit, 1, 1, 10.0
+withdrawal, 1, 2, 2.5
+";
+        let (txs, report) = process_transactions(format!("\u{feff}{}", data).as_bytes()).unwrap();
+        let (expected, _) = process_transactions(data.as_bytes()).unwrap();
+
+        assert_eq!(report.processed, 2);
+        assert!(report.errors.is_empty());
+        assert_eq!(txs.accounts, expected.accounts);
+        assert_eq!(txs.accounts[&1], Account::new(dec!(7.5), dec!(0), false));
+    }
+
+    #[test]
+    fn test_process_transactions_parallel() {
+        let data = "\
+type, client, tx, amount
+deposit, 1, 1, 10.0
+deposit, 2, 2, 4.0
+deposit, 3, 3, 7.0
+withdrawal, 1, 4, 2.5
+deposit, 2, 1, 1.0
+dispute, 2, 2,
+withdrawal, 2, 5, 1.0
+dispute, 3, 3,
+deposit, 4, 6, 3.0
+chargeback, 3, 3,
+deposit, 3, 7, 1.0
+withdrawal, 4, 8, 5.0
+deposit, 1, 6, 1.0
+dispute, 1, 1,
+resolve, 1, 1,
+withdrawal, 1, 9, 7.5
+";
+        let (expected, expected_report) = process_transactions(data.as_bytes()).unwrap();
+        for threads in [0, 1, 2, 3, 8] {
+            let (txs, report) = process_transactions_parallel(data.as_bytes(), threads).unwrap();
+            assert_eq!(txs.accounts, expected.accounts);
+            let mut txids: Vec<_> = txs.txs.keys().collect();
+            let mut expected_txids: Vec<_> = expected.txs.keys().collect();
+            txids.sort();
+            expected_txids.sort();
+            assert_eq!(txids, expected_txids);
+            assert_eq!(report, expected_report);
+        }
+        assert_eq!(
+            expected_report
+                .errors
+                .iter()
+                .map(|error| (error.line, &error.error))
+                .collect::<Vec<_>>(),
+            vec![
+                (6, &Error::TxAlreadyExists),
+                (8, &Error::InsuffienctFunds),
+                (12, &Error::AccountIsLocked),
+                (13, &Error::InsuffienctFunds),
+                (14, &Error::TxAlreadyExists),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_process_transactions_with_unparseable_row() {
+        let data = "\
+type, client, tx, amount
+deposit, 1, 1, 1.0
+deposit, 1, 2, abc
+refund, 1, 3, 1.0
+deposit, 1, 4, 2.0
+";
+        let (txs, report) = process_transactions(data.as_bytes()).unwrap();
+        assert_eq!(report.processed, 4);
+        assert_eq!(
+            report.errors,
+            vec![
+                LineError {
+                    line: 3,
+                    error: Error::InvalidTx,
+                    raw: "deposit, 1, 2, abc".to_string(),
+                },
+                LineError {
+                    line: 4,
+                    error: Error::InvalidTx,
+                    raw: "refund, 1, 3, 1.0".to_string(),
+                },
+            ]
+        );
+        assert_eq!(txs.accounts[&1], Account::new(dec!(3), dec!(0), false));
+    }
+
+    #[test]
+    fn test_process_report_counts() {
+        let data = "\
+type, client, tx, amount
+deposit, 1, 1, 10.0
+deposit, 2, 2, 4.0
+deposit, 1, 1, 3.0
+withdrawal, 2, 3, 5.0
+withdrawal, 1, 4, 2.5
+deposit, 2, 2, 1.0
+withdrawal, 1, 5, 100.0
+dispute, 2, 2,
+";
+        let (_, report) = process_transactions(data.as_bytes()).unwrap();
+        assert_eq!(report.processed, 8);
+        assert_eq!(report.accepted, 4);
+        assert_eq!(report.rejected, 4);
+        assert_eq!(
+            report.by_error,
+            HashMap::from([(Error::TxAlreadyExists, 2), (Error::InsuffienctFunds, 2)])
+        );
+        assert_eq!(report.errors.len(), report.rejected);
+    }
+
+    #[test]
+    fn test_process_transactions_with_progress() {
+        let data = "\
+type, client, tx, amount
+deposit, 1, 1, 10.0
+deposit, 1, 1, 10.0
+withdrawal, 1, 2, 2.5
+deposit, 1, 3, abc
+dispute, 1, 1,
+";
+        let mut lines = Vec::new();
+        let (txs, report) =
+            process_transactions_with_progress(data.as_bytes(), |line| lines.push(line)).unwrap();
+        assert_eq!(lines, vec![2, 3, 4, 5, 6]);
+        assert_eq!(lines.len(), report.processed);
+        assert_eq!(report.rejected, 2);
+        assert_eq!(txs.accounts[&1], Account::new(dec!(-2.5), dec!(10), false));
+    }
+
+    #[test]
+    fn test_write_raw_transactions() {
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, dec!(10.5)).unwrap();
+        txs.dispute(1, 1001).unwrap();
+        let stream = vec![
+            txs.txs[&1001].clone(),
+            Tx::withdrawal(2, 1002, dec!(3)),
+            Tx::charge_back(1, 1001),
+        ];
+        let mut buf = vec![];
+
+        write_raw_transactions(&stream, &mut buf).unwrap();
+
+        let output = std::str::from_utf8(&buf).unwrap();
+        assert_eq!(
+            output,
+            "type,client,tx,amount\ndeposit,1,1001,10.5\nwithdrawal,2,1002,3\nchargeback,1,1001,\n"
+        );
+        let parsed: Vec<Tx> = output
+            .lines()
+            .skip(1)
+            .map(|line| line.parse().unwrap())
+            .collect();
+        assert_eq!(parsed, stream);
+
+        let (replayed, report) = process_transactions(output.as_bytes()).unwrap();
+        assert_eq!(report.rejected, 2);
+        assert_eq!(
+            replayed.accounts[&1],
+            Account::new(dec!(10.5), dec!(0), false)
+        );
+    }
 }
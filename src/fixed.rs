@@ -0,0 +1,151 @@
+//! The `fixed` module is used to read transactions from a
+//! fixed-width buffer, _e.g._, a legacy mainframe feed.
+
+#![warn(missing_docs)]
+
+use std::{
+    error,
+    io::{self, BufRead},
+    ops::Range,
+    str::FromStr,
+};
+
+use rust_decimal::Decimal;
+
+use crate::{csv::ProcessReport, Cid, Error, IdField, Tx, TxKind, Txid, Txs};
+
+/// Describes the byte ranges of each field in a fixed-width transaction line.
+///
+/// Fields are trimmed after being sliced,
+/// so they can be padded with spaces on either side.
+/// A field range that extends past the end of a line is truncated,
+/// _e.g._, disputes may omit the trailing amount.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FixedLayout {
+    /// The byte range of the transaction type.
+    pub kind: Range<usize>,
+    /// The byte range of the client ID.
+    pub client: Range<usize>,
+    /// The byte range of the transaction ID.
+    pub tx: Range<usize>,
+    /// The byte range of the amount, empty for disputes, resolves and chargebacks.
+    pub amount: Range<usize>,
+}
+
+impl FixedLayout {
+    /// Returns the trimmed field of `line` at `range`,
+    /// or `None` if the range does not fall on character boundaries.
+    fn field<'a>(line: &'a str, range: &Range<usize>) -> Option<&'a str> {
+        let end = range.end.min(line.len());
+        let start = range.start.min(end);
+        line.get(start..end).map(str::trim)
+    }
+
+    /// Parses a fixed-width `line` into a `Tx` according to this layout.
+    fn parse(&self, line: &str) -> Result<Tx, Error> {
+        let field = |range| Self::field(line, range).ok_or(Error::InvalidTx);
+        let kind: TxKind = field(&self.kind)?.parse()?;
+        let client: u64 = field(&self.client)?.parse().map_err(|_| Error::InvalidTx)?;
+        let tx: u64 = field(&self.tx)?.parse().map_err(|_| Error::InvalidTx)?;
+        let amount = match field(&self.amount)? {
+            "" => None,
+            amount => Some(Decimal::from_str(amount).map_err(|_| Error::InvalidTx)?),
+        };
+        let cid = Cid::try_from(client).map_err(|_| Error::IdOutOfRange(IdField::Client))?;
+        let txid = Txid::try_from(tx).map_err(|_| Error::IdOutOfRange(IdField::Tx))?;
+        Ok(Tx::new(kind, cid, txid, amount))
+    }
+}
+
+/// Parses and processes incoming fixed-width transactions from a file,
+/// slicing each line according to `layout`.
+///
+/// The buffer has no header and blank lines are ignored.
+/// Lines that cannot be parsed are rejected with `Error::InvalidTx`.
+/// As in [`crate::csv::process_transactions`], transactions that cannot be processed
+/// are skipped and logged as warnings, and reported in the returned `ProcessReport`.
+///
+/// # Examples
+///
+/// ```
+/// use toy_payments_engine::fixed::*;
+/// use rust_decimal_macros::dec;
+///
+/// let layout = FixedLayout { kind: 0..10, client: 10..15, tx: 15..25, amount: 25..40 };
+/// let data = "\
+/// deposit       1      1001         10.5
+/// withdrawal    1      1002            3
+/// ";
+///
+/// let (txs, report) = process_transactions_fixed(data.as_bytes(), layout).unwrap();
+/// assert_eq!(report.processed, 2);
+/// assert_eq!(txs.get(1).unwrap().available, dec!(7.5));
+/// ```
+pub fn process_transactions_fixed<R: io::Read>(
+    rdr: R,
+    layout: FixedLayout,
+) -> Result<(Txs, ProcessReport), Box<dyn error::Error>> {
+    let mut txs = Txs::new();
+    let mut report = ProcessReport::default();
+    for (lineno, line) in (1..).zip(io::BufReader::new(rdr).lines()) {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match layout.parse(&line).and_then(|tx| txs.process_tx(tx)) {
+            Ok(()) => report.accept(),
+            Err(err) => report.reject(lineno, line, err),
+        }
+    }
+
+    Ok((txs, report))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use rust_decimal_macros::dec;
+
+    use crate::{csv::LineError, Account, Error};
+
+    use super::{process_transactions_fixed, FixedLayout};
+
+    #[test]
+    fn test_process_transactions_fixed() {
+        let layout = FixedLayout {
+            kind: 0..10,
+            client: 10..15,
+            tx: 15..25,
+            amount: 25..40,
+        };
+        let data = "\
+deposit       1      1001         10.5
+deposit       2      1002            2
+withdrawal    1      1003            3
+
+dispute       2      1002
+withdrawal    2      1004            1
+unknown       1      1005            1
+resolve       2      1002
+";
+        let (txs, report) = process_transactions_fixed(data.as_bytes(), layout).unwrap();
+        assert_eq!(report.processed, 7);
+        assert_eq!(
+            report.errors,
+            vec![
+                LineError {
+                    line: 6,
+                    error: Error::InsuffienctFunds,
+                    raw: "withdrawal    2      1004            1".to_string(),
+                },
+                LineError {
+                    line: 7,
+                    error: Error::InvalidTx,
+                    raw: "unknown       1      1005            1".to_string(),
+                },
+            ]
+        );
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(7.5), dec!(0), false)));
+        assert_eq!(txs.get(2), Some(&Account::new(dec!(2), dec!(0), false)));
+    }
+}
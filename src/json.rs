@@ -0,0 +1,167 @@
+//! The `json` module is used to read transactions from a JSON Lines buffer,
+//! and to write accounts to a JSON buffer, _e.g._, a file or a string.
+
+#![warn(missing_docs)]
+
+use std::{
+    error,
+    io::{self, BufRead},
+};
+
+use crate::{
+    csv::{account_records, ProcessReport, TxRecord},
+    Error, Txs,
+};
+
+/// Parses and processes incoming transactions from a JSON Lines file,
+/// _i.e._, one JSON object per line.
+///
+/// Objects have the same fields as the CSV columns, _i.e._,
+/// `type`, `client`, `tx` and an optional `amount`.
+/// Blank lines are ignored, while a line that is not a valid transaction
+/// is rejected with `Error::InvalidTx`.
+/// As in [`crate::csv::process_transactions`], transactions that cannot be processed
+/// are skipped and logged as warnings, and reported in the returned `ProcessReport`.
+///
+/// # Examples
+///
+/// ```
+/// use toy_payments_engine::json::*;
+/// use rust_decimal_macros::dec;
+///
+/// let data = r#"
+/// {"type": "deposit", "client": 1, "tx": 1001, "amount": "10.5"}
+/// {"type": "withdrawal", "client": 1, "tx": 1002, "amount": "3"}
+/// "#;
+///
+/// let (txs, report) = process_transactions_jsonl(data.as_bytes()).unwrap();
+/// assert_eq!(report.processed, 2);
+/// assert_eq!(txs.get(1).unwrap().available, dec!(7.5));
+/// ```
+pub fn process_transactions_jsonl<R: io::Read>(
+    rdr: R,
+) -> Result<(Txs, ProcessReport), Box<dyn error::Error>> {
+    let mut txs = Txs::new();
+    let mut report = ProcessReport::default();
+    for (lineno, line) in (1..).zip(io::BufReader::new(rdr).lines()) {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str::<TxRecord>(&line).map_err(|_| Error::InvalidTx);
+        match record
+            .and_then(TxRecord::into_tx)
+            .and_then(|tx| txs.process_tx(tx))
+        {
+            Ok(()) => report.accept(),
+            Err(err) => report.reject(lineno, line, err),
+        }
+    }
+
+    Ok((txs, report))
+}
+
+/// Writes the accounts in `txs` to a `Write`r `wtr` as a JSON array.
+///
+/// Each account is written as an object with the fields `client`, `available`, `held`,
+/// `total` and `locked`, sorted by client ID as in [`crate::csv::write_transactions`].
+/// Balances are written as strings to avoid any loss of precision.
+///
+/// # Examples
+///
+/// ```
+/// use toy_payments_engine::*;
+/// use toy_payments_engine::json::*;
+/// use rust_decimal_macros::dec;
+///
+/// let mut txs = Txs::new();
+/// let mut buf = vec![];
+///
+/// txs.deposit(1, 1001, dec!(1.5)).unwrap();
+///
+/// write_transactions_json(&txs, &mut buf).unwrap();
+///
+/// assert_eq!(
+///     std::str::from_utf8(&buf).unwrap(),
+///     r#"[{"client":1,"available":"1.5","held":"0","total":"1.5","locked":false}]"#
+/// );
+/// ```
+pub fn write_transactions_json<W: io::Write>(
+    txs: &Txs,
+    mut wtr: W,
+) -> Result<(), Box<dyn error::Error>> {
+    let records = account_records(txs.accounts(), false)?;
+    serde_json::to_writer(&mut wtr, &records)?;
+    wtr.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use rust_decimal_macros::dec;
+
+    use crate::{csv::process_transactions, Error, Txs};
+
+    use super::{process_transactions_jsonl, write_transactions_json};
+
+    #[test]
+    fn test_process_transactions_jsonl() {
+        let jsonl = r#"{"type": "deposit", "client": 1, "tx": 1, "amount": "5.25"}
+{"type": "deposit", "client": 1, "tx": 2, "amount": 2}
+{"type": "dispute", "client": 1, "tx": 1}
+"#;
+        let csv = "\
+type, client, tx, amount
+deposit, 1, 1, 5.25
+deposit, 1, 2, 2
+dispute, 1, 1,
+";
+        let (txs, report) = process_transactions_jsonl(jsonl.as_bytes()).unwrap();
+        let (expected, _) = process_transactions(csv.as_bytes()).unwrap();
+        assert_eq!(report.processed, 3);
+        assert!(report.errors.is_empty());
+        assert_eq!(txs.accounts, expected.accounts);
+        assert_eq!(txs.get(1).unwrap().held, dec!(5.25));
+    }
+
+    #[test]
+    fn test_process_transactions_jsonl_invalid_line() {
+        let jsonl = r#"{"type": "deposit", "client": 1, "tx": 1, "amount": "5"}
+{"type": "withdrawal", "client": 1, "tx": 2, "amount": "6"}
+"#;
+        let (_, report) = process_transactions_jsonl(jsonl.as_bytes()).unwrap();
+        assert_eq!(report.errors[0].line, 2);
+        assert_eq!(report.errors[0].error, Error::InsuffienctFunds);
+
+        let jsonl = format!(
+            "{}not json\n{{\"type\": \"deposit\", \"client\": 1, \"tx\": 3, \"amount\": \"1\"}}\n",
+            jsonl
+        );
+        let (txs, report) = process_transactions_jsonl(jsonl.as_bytes()).unwrap();
+        assert_eq!(report.processed, 4);
+        assert_eq!(report.errors[1].line, 3);
+        assert_eq!(report.errors[1].error, Error::InvalidTx);
+        assert_eq!(report.errors[1].raw, "not json");
+        assert_eq!(txs.get(1).unwrap().available, dec!(6));
+    }
+
+    #[test]
+    fn test_write_transactions_json() {
+        let mut txs = Txs::new();
+        txs.deposit(2, 1001, dec!(3)).unwrap();
+        txs.deposit(1, 1002, dec!(10.25)).unwrap();
+        txs.withdrawal(1, 1003, dec!(0.25)).unwrap();
+        txs.dispute(2, 1001).unwrap();
+
+        let mut buf = Vec::new();
+        write_transactions_json(&txs, &mut buf).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            concat!(
+                r#"[{"client":1,"available":"10","held":"0","total":"10","locked":false},"#,
+                r#"{"client":2,"available":"0","held":"3","total":"3","locked":false}]"#
+            )
+        );
+    }
+}
@@ -4,17 +4,32 @@
 #![warn(missing_docs)]
 
 pub mod csv;
+pub mod fixed;
+pub mod json;
 
-use std::collections::{hash_map::Entry, HashMap};
+use std::{
+    collections::{HashMap, HashSet},
+    error, fmt,
+    str::FromStr,
+    sync::Arc,
+};
 
-use rust_decimal::Decimal;
-use serde::Deserialize;
+use log::warn;
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Serialize};
 
 type Txid = u32;
 
 type Cid = u16;
 
-#[derive(Debug, PartialEq, Clone, Copy, Deserialize)]
+/// A function applied to the amount of every incoming deposit and withdrawal.
+/// See `TxsBuilder::amount_transform`.
+pub type AmountTransform = Arc<dyn Fn(Decimal) -> Decimal + Send + Sync>;
+
+/// The maximum number of decimal places allowed in the amount of a deposit or withdrawal.
+pub const MAX_DECIMAL_PLACES: u32 = 4;
+
+#[derive(Debug, PartialEq, Clone, Copy, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase")]
 /// Represents the kind of transactions that can be processed.
 pub enum TxKind {
@@ -28,10 +43,35 @@ pub enum TxKind {
     Resolve,
     /// A chargeback is the final state of a dispute and represents the client reversing a transaction.
     ChargeBack,
+    /// A correction replaces the amount of a prior deposit that was recorded with a wrong amount.
+    Correction,
+    /// A freeze administratively locks a client's account, without a disputed transaction.
+    Freeze,
+}
+
+/// Represents the dispute lifecycle of a stored transaction.
+#[derive(Debug, PartialEq, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DisputeState {
+    /// The transaction has never been disputed.
+    #[default]
+    Undisputed,
+    /// The transaction is currently being disputed, and its funds are held.
+    Disputed,
+    /// The dispute on this transaction was resolved, releasing the held funds.
+    Resolved,
+    /// The dispute on this transaction was finalized with a chargeback.
+    ChargedBack,
 }
 
 /// Represents an incoming transaction.
-#[derive(Debug, Deserialize)]
+///
+/// Two transactions are equal when they have the same kind, client ID, transaction ID and amount.
+/// Their dispute state is not compared, so a stored transaction equals the incoming one
+/// even after being disputed.
+///
+/// Likewise, only these fields are serialized.
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Tx {
     /// The transaction kind of this `tx`.
     #[serde(rename = "type")]
@@ -41,11 +81,41 @@ pub struct Tx {
     #[serde(rename = "tx")]
     txid: Txid,
     amount: Option<Decimal>,
-    #[serde(skip_deserializing)]
-    disputed: bool,
+    #[serde(skip)]
+    state: DisputeState,
+    #[serde(skip)]
+    held: Decimal,
+    #[serde(skip)]
+    charged_back: Decimal,
+    #[serde(skip)]
+    seq: u64,
+}
+
+impl PartialEq for Tx {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind
+            && self.cid == other.cid
+            && self.txid == other.txid
+            && self.amount == other.amount
+    }
 }
 
+impl Eq for Tx {}
+
 impl Tx {
+    fn new(kind: TxKind, cid: Cid, txid: Txid, amount: Option<Decimal>) -> Self {
+        Self {
+            kind,
+            cid,
+            txid,
+            amount,
+            state: DisputeState::Undisputed,
+            held: Decimal::ZERO,
+            charged_back: Decimal::ZERO,
+            seq: 0,
+        }
+    }
+
     /// Creates a new incoming deposit transaction.
     ///
     /// # Examples
@@ -55,13 +125,7 @@ impl Tx {
     /// assert_eq!(Tx::deposit(1, 1000, rust_decimal_macros::dec!(1)).kind, TxKind::Deposit);
     /// ```
     pub fn deposit(cid: Cid, txid: Txid, amount: Decimal) -> Self {
-        Self {
-            kind: TxKind::Deposit,
-            cid,
-            txid,
-            amount: Some(amount),
-            disputed: false,
-        }
+        Self::new(TxKind::Deposit, cid, txid, Some(amount))
     }
 
     /// Creates a new incoming withdrawal transaction.
@@ -73,57 +137,86 @@ impl Tx {
     /// assert_eq!(Tx::withdrawal(1, 1000, rust_decimal_macros::dec!(1)).kind, TxKind::Withdrawal);
     /// ```
     pub fn withdrawal(cid: Cid, txid: Txid, amount: Decimal) -> Self {
-        Self {
-            kind: TxKind::Withdrawal,
-            cid,
-            txid,
-            amount: Some(amount),
-            disputed: false,
-        }
+        Self::new(TxKind::Withdrawal, cid, txid, Some(amount))
     }
 
     /// Creates a new incoming dispute transaction.
     /// Please note that this type of transaction does not take an amount.
     /// The amount is taken from the corresponding `txid`.
     pub fn dispute(cid: Cid, txid: Txid) -> Self {
-        Self {
-            kind: TxKind::Dispute,
-            cid,
-            txid,
-            amount: None,
-            disputed: false,
-        }
+        Self::new(TxKind::Dispute, cid, txid, None)
     }
 
     /// Creates a new incoming resolve transaction.
     /// Please note that this type of transaction does not take an amount.
     /// The amount is taken from the corresponding `txid`.
     pub fn resolve(cid: Cid, txid: Txid) -> Self {
-        Self {
-            kind: TxKind::Resolve,
-            cid,
-            txid,
-            amount: None,
-            disputed: false,
-        }
+        Self::new(TxKind::Resolve, cid, txid, None)
     }
 
     /// Creates a new incoming chargeback transaction.
     /// Please note that this type of transaction does not take an amount.
     /// The amount is taken from the corresponding `txid`.
     pub fn charge_back(cid: Cid, txid: Txid) -> Self {
-        Self {
-            kind: TxKind::ChargeBack,
-            cid,
-            txid,
-            amount: None,
-            disputed: false,
-        }
+        Self::new(TxKind::ChargeBack, cid, txid, None)
+    }
+
+    /// Creates a new incoming freeze transaction.
+    /// Please note that this type of transaction does not reference any transaction,
+    /// so its transaction ID is `0`.
+    pub fn freeze(cid: Cid) -> Self {
+        Self::new(TxKind::Freeze, cid, 0, None)
+    }
+
+    /// Creates a new incoming correction transaction.
+    /// The `amount` is the corrected amount of the deposit referenced by `txid`.
+    pub fn correction(cid: Cid, txid: Txid, amount: Decimal) -> Self {
+        Self::new(TxKind::Correction, cid, txid, Some(amount))
+    }
+
+    /// Returns the client ID of this transaction.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use toy_payments_engine::*;
+    /// assert_eq!(Tx::deposit(1, 1000, rust_decimal_macros::dec!(1)).cid(), 1);
+    /// ```
+    pub fn cid(&self) -> Cid {
+        self.cid
+    }
+
+    /// Returns the transaction ID of this transaction.
+    /// For disputes, resolves and chargebacks, this is the ID of the referenced transaction.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use toy_payments_engine::*;
+    /// assert_eq!(Tx::dispute(1, 1000).txid(), 1000);
+    /// ```
+    pub fn txid(&self) -> Txid {
+        self.txid
+    }
+
+    /// Returns the amount of this transaction, if any.
+    /// Disputes, resolves and chargebacks do not have an amount.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use toy_payments_engine::*;
+    /// use rust_decimal_macros::dec;
+    /// assert_eq!(Tx::withdrawal(1, 1000, dec!(2.5)).amount(), Some(dec!(2.5)));
+    /// assert_eq!(Tx::resolve(1, 1000).amount(), None);
+    /// ```
+    pub fn amount(&self) -> Option<Decimal> {
+        self.amount
     }
 }
 
 /// Represents the state of a given client's account.
-#[derive(Debug, PartialEq, Default)]
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
 pub struct Account {
     /// The funds that are available for trading, staking, withdrawal, _etc_.
     pub available: Decimal,
@@ -143,9 +236,136 @@ impl Account {
             locked,
         }
     }
+
+    /// Returns the total funds, _i.e._, `available + held`,
+    /// or `None` if the addition overflows.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toy_payments_engine::*;
+    /// # use rust_decimal::Decimal;
+    /// # use rust_decimal_macros::dec;
+    /// assert_eq!(Account::new(dec!(10), dec!(2.5), false).total(), Some(dec!(12.5)));
+    /// assert_eq!(Account::new(Decimal::MAX, dec!(1), false).total(), None);
+    /// ```
+    pub fn total(&self) -> Option<Decimal> {
+        self.available.checked_add(self.held)
+    }
+
+    /// Whether this account has no available nor held funds and is not locked.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toy_payments_engine::*;
+    /// # use rust_decimal_macros::dec;
+    /// assert!(Account::new(dec!(0), dec!(0), false).is_empty());
+    /// assert!(!Account::new(dec!(0), dec!(0), true).is_empty());
+    /// assert!(!Account::new(dec!(0), dec!(1), false).is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.available.is_zero() && self.held.is_zero() && !self.locked
+    }
+}
+
+/// Represents the state of a client's account at a point in time.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct AccountSnapshot {
+    /// The client ID of this account.
+    pub client: Cid,
+    /// The funds that were available.
+    pub available: Decimal,
+    /// The funds that were held for dispute.
+    pub held: Decimal,
+    /// Whether the account was locked.
+    pub locked: bool,
+}
+
+impl AccountSnapshot {
+    fn new(cid: Cid, account: &Account) -> Self {
+        Self {
+            client: cid,
+            available: account.available,
+            held: account.held,
+            locked: account.locked,
+        }
+    }
+
+    /// Whether this snapshot differs from the current state of `account`.
+    pub fn differs_from(&self, account: &Account) -> bool {
+        self.available != account.available
+            || self.held != account.held
+            || self.locked != account.locked
+    }
+}
+
+/// Represents the state of every account in a `Txs` at a point in time.
+///
+/// Use `Txs::engine_snapshot` to take a snapshot.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct EngineSnapshot {
+    accounts: HashMap<Cid, AccountSnapshot>,
+}
+
+impl EngineSnapshot {
+    /// Returns the snapshot of a client's account if it existed, otherwise `None`.
+    pub fn get(&self, cid: Cid) -> Option<&AccountSnapshot> {
+        self.accounts.get(&cid)
+    }
+}
+
+/// Represents the aggregate flow of funds through a `Txs`,
+/// relating the balances of every account to the stored transactions.
+///
+/// Use `Txs::conservation_report` to compute it.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ConservationReport {
+    /// The total amount of stored deposits, including any corrections.
+    pub deposited: Decimal,
+    /// The total amount of stored withdrawals.
+    pub withdrawn: Decimal,
+    /// The total funds charged back from deposits, including partial chargebacks.
+    pub charged_back: Decimal,
+    /// The total available funds across every account.
+    pub available: Decimal,
+    /// The total held funds across every account.
+    pub held: Decimal,
+    /// Whether `available + held` equals `deposited - withdrawn - charged_back`.
+    pub balanced: bool,
+}
+
+/// Represents a stored transaction in a `StateSnapshot`, along with its dispute state.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredTx {
+    #[serde(rename = "type")]
+    kind: TxKind,
+    client: Cid,
+    tx: Txid,
+    amount: Option<Decimal>,
+    state: DisputeState,
+    held: Decimal,
+    #[serde(default)]
+    charged_back: Decimal,
+    seq: u64,
+}
+
+/// Represents the whole state of a `Txs`, _i.e._, its accounts and stored transactions,
+/// that can be serialized to persist it across program restarts.
+///
+/// Use `Txs::snapshot` to take a snapshot, and `Txs::restore` to restore it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateSnapshot {
+    accounts: Vec<(Cid, Account)>,
+    txs: Vec<StoredTx>,
+    seen_txids: Vec<Txid>,
+    next_seq: u64,
+    max_txid: Option<Txid>,
+    disputes_enabled: bool,
+    overdraft_limits: Vec<(Cid, Decimal)>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 /// Represents the kind of errors returned by `Txs::process_tx`.
 pub enum Error {
     /// Occurs when the amount is either ZERO or a negative value.
@@ -164,19 +384,405 @@ pub enum Error {
     TxAlreadyDisputed,
     /// Occurs when a TX is not being disputed.
     TxNotDisputed,
+    /// Occurs when a TX dispute was already finalized by a chargeback.
+    AlreadyChargedBack,
     /// Occurs when a withdrawal TX is being disputed.
     TxMustBeDeposit,
-    /// Occurs when the account is currently locked because of a previous charge back.
+    /// Occurs when the account is currently locked, either because of a previous charge back
+    /// or because it was frozen.
     AccountIsLocked,
     /// When transaction is not well formed.
     InvalidTx,
+    /// Occurs when the client does not have an account.
+    AccountNotFound,
+    /// Occurs when a transaction ID is not greater than the previously processed ones.
+    NonMonotonicTxid,
+    /// Occurs when an amount has more than `MAX_DECIMAL_PLACES` decimal places.
+    TooManyDecimalPlaces,
+    /// Occurs when the amount to release exceeds the funds held for dispute.
+    AmountExceedsHeld,
+    /// Occurs when an incoming ID does not fit in its type, _e.g._, a client ID above `u16::MAX`.
+    IdOutOfRange(IdField),
+    /// Occurs when a dispute is received while disputes are disabled.
+    DisputesDisabled,
+    /// Occurs when a withdrawal would leave no available funds while some funds are held.
+    WouldStrandHeldFunds,
+    /// Occurs when the funds held for a dispute exceed the account's held funds.
+    HeldUnderflow,
+    /// Occurs when the disputed deposit is not among the client's most recent deposits.
+    DisputeWindowExpired,
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+/// Represents the ID fields of an incoming transaction.
+pub enum IdField {
+    /// The client ID field, _i.e._, the `client` column.
+    Client,
+    /// The transaction ID field, _i.e._, the `tx` column.
+    Tx,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidAmount => write!(f, "amount must be a positive value"),
+            Error::MathError => write!(f, "arithmetic overflow or underflow in account funds"),
+            Error::InsuffienctFunds => write!(f, "insufficient available funds for this operation"),
+            Error::TxAlreadyExists => write!(f, "transaction ID was already processed"),
+            Error::TxNotFound => write!(f, "transaction ID was not found"),
+            Error::CidMismatch => write!(
+                f,
+                "client ID does not match the client of the referenced transaction"
+            ),
+            Error::TxAlreadyDisputed => write!(f, "transaction is already being disputed"),
+            Error::TxNotDisputed => write!(f, "transaction is not being disputed"),
+            Error::AlreadyChargedBack => {
+                write!(
+                    f,
+                    "transaction dispute was already finalized by a chargeback"
+                )
+            }
+            Error::TxMustBeDeposit => write!(f, "only deposit transactions can be disputed"),
+            Error::AccountIsLocked => write!(f, "account is locked"),
+            Error::InvalidTx => write!(f, "transaction is not well formed"),
+            Error::AccountNotFound => write!(f, "client account was not found"),
+            Error::NonMonotonicTxid => write!(
+                f,
+                "transaction ID is not greater than the previously processed ones"
+            ),
+            Error::TooManyDecimalPlaces => write!(
+                f,
+                "amount has more than {} decimal places",
+                MAX_DECIMAL_PLACES
+            ),
+            Error::AmountExceedsHeld => write!(f, "amount exceeds the funds held for dispute"),
+            Error::IdOutOfRange(field) => write!(f, "{} ID is out of range", field),
+            Error::DisputesDisabled => write!(f, "disputes are disabled"),
+            Error::WouldStrandHeldFunds => {
+                write!(f, "withdrawal would leave only held funds in the account")
+            }
+            Error::HeldUnderflow => write!(f, "disputed funds exceed the held funds"),
+            Error::DisputeWindowExpired => {
+                write!(f, "deposit is too old to be disputed")
+            }
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+/// Represents a failure of `Txs::audit`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum AuditError {
+    /// The held funds of all accounts differ from the funds held for open disputes.
+    HeldMismatch {
+        /// The funds held for every disputed transaction.
+        expected_held: Decimal,
+        /// The held funds across every account.
+        actual_held: Decimal,
+    },
+    /// The held funds overflow when summed, so they cannot be compared.
+    MathError,
+}
+
+impl fmt::Display for AuditError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuditError::HeldMismatch {
+                expected_held,
+                actual_held,
+            } => write!(
+                f,
+                "accounts hold {} but disputed transactions hold {}",
+                actual_held, expected_held
+            ),
+            AuditError::MathError => write!(f, "arithmetic overflow in held funds"),
+        }
+    }
+}
+
+impl error::Error for AuditError {}
+
+impl fmt::Display for TxKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TxKind::Deposit => write!(f, "deposit"),
+            TxKind::Withdrawal => write!(f, "withdrawal"),
+            TxKind::Dispute => write!(f, "dispute"),
+            TxKind::Resolve => write!(f, "resolve"),
+            TxKind::ChargeBack => write!(f, "chargeback"),
+            TxKind::Correction => write!(f, "correction"),
+            TxKind::Freeze => write!(f, "freeze"),
+        }
+    }
+}
+
+/// Parses a transaction kind from its token, as written by its `Display` implementation.
+/// An unknown token is rejected with `Error::InvalidTx`.
+impl FromStr for TxKind {
+    type Err = Error;
+
+    fn from_str(token: &str) -> Result<Self, Self::Err> {
+        match token {
+            "deposit" => Ok(TxKind::Deposit),
+            "withdrawal" => Ok(TxKind::Withdrawal),
+            "dispute" => Ok(TxKind::Dispute),
+            "resolve" => Ok(TxKind::Resolve),
+            "chargeback" => Ok(TxKind::ChargeBack),
+            "correction" => Ok(TxKind::Correction),
+            "freeze" => Ok(TxKind::Freeze),
+            _ => Err(Error::InvalidTx),
+        }
+    }
+}
+
+impl fmt::Display for IdField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IdField::Client => write!(f, "client"),
+            IdField::Tx => write!(f, "transaction"),
+        }
+    }
+}
+
+/// Represents the policies used by a `Txs` to process transactions.
+///
+/// Use a `TxsBuilder` to create a `Txs` with a non-default `Config`.
+#[derive(Clone, Default)]
+pub struct Config {
+    /// Whether transactions for clients not added with `Txs::register_client` are rejected.
+    /// When disabled, accounts are implicitly created on the first transaction of a client.
+    pub require_registered_clients: bool,
+    /// Whether deposits and withdrawals must have strictly increasing transaction IDs.
+    /// Disputes, resolves and chargebacks reference existing transactions and are exempt.
+    pub require_monotonic_txids: bool,
+    /// Clients whose accounts are not locked on chargeback, _e.g._, pending a manual review.
+    /// The held funds of a chargeback are still reversed for these clients.
+    pub no_lock_clients: HashSet<Cid>,
+    /// The rounding strategy used for deposit and withdrawal amounts with more than
+    /// `MAX_DECIMAL_PLACES` decimal places.
+    /// When `None`, these amounts are rejected with `Error::TooManyDecimalPlaces`.
+    pub amount_rounding: Option<RoundingStrategy>,
+    /// Whether only disputable transactions, _i.e._, deposits, are stored.
+    /// The IDs of other transactions are still tracked to reject duplicates,
+    /// but the transactions themselves are dropped to reduce memory usage.
+    /// Note that disputing a withdrawal then fails with `Error::TxNotFound`,
+    /// and that withdrawals are not included in ledger exports.
+    pub store_disputable_only: bool,
+    /// The transformation applied to the raw amount of every deposit and withdrawal,
+    /// _e.g._, FX conversion or unit scaling, before it is validated, stored and applied.
+    pub amount_transform: Option<AmountTransform>,
+    /// Whether a withdrawal that would leave zero available funds
+    /// while some funds are still held is rejected.
+    pub block_withdrawal_to_zero_with_held: bool,
+    /// Whether a dispute that would make the available funds negative,
+    /// _e.g._, because the disputed deposit was already withdrawn, is rejected.
+    pub strict_disputes: bool,
+    /// Whether deposits and withdrawals of a zero amount, _e.g._, heartbeats, are ignored.
+    /// Ignored transactions succeed without consuming their transaction ID
+    /// or creating an account, instead of being rejected with `Error::InvalidAmount`.
+    pub ignore_zero_amount: bool,
+    /// The number of most recent deposits of a client that can be disputed.
+    /// Older deposits are considered settled.
+    /// When `None`, every stored deposit can be disputed.
+    pub disputable_window: Option<usize>,
+    /// Whether a deposit or withdrawal can reuse the ID of a charged-back transaction,
+    /// _e.g._, for a corrected resubmission, replacing the charged-back transaction.
+    pub allow_txid_reuse_after_chargeback: bool,
+    /// Whether a transaction can be disputed at most once,
+    /// so that a resolved dispute cannot be raised again.
+    pub single_dispute: bool,
+    /// The overdraft limit of every client without its own limit,
+    /// _i.e._, how far below zero its available funds can go.
+    /// See `Txs::set_overdraft_limit` to override it for a single client.
+    pub overdraft_limit: Decimal,
+}
+
+impl fmt::Debug for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Config")
+            .field(
+                "require_registered_clients",
+                &self.require_registered_clients,
+            )
+            .field("require_monotonic_txids", &self.require_monotonic_txids)
+            .field("no_lock_clients", &self.no_lock_clients)
+            .field("amount_rounding", &self.amount_rounding)
+            .field("store_disputable_only", &self.store_disputable_only)
+            .field(
+                "amount_transform",
+                &self
+                    .amount_transform
+                    .as_ref()
+                    .map(|_| "Fn(Decimal) -> Decimal"),
+            )
+            .field(
+                "block_withdrawal_to_zero_with_held",
+                &self.block_withdrawal_to_zero_with_held,
+            )
+            .field("strict_disputes", &self.strict_disputes)
+            .field("ignore_zero_amount", &self.ignore_zero_amount)
+            .field("disputable_window", &self.disputable_window)
+            .field(
+                "allow_txid_reuse_after_chargeback",
+                &self.allow_txid_reuse_after_chargeback,
+            )
+            .field("single_dispute", &self.single_dispute)
+            .field("overdraft_limit", &self.overdraft_limit)
+            .finish()
+    }
+}
+
+/// Builds a `Txs` with custom processing policies.
+///
+/// # Examples
+///
+/// ```
+/// # use toy_payments_engine::*;
+/// let txs = Txs::builder().require_registered_clients(true).build();
+/// ```
+#[derive(Debug, Default)]
+pub struct TxsBuilder {
+    config: Config,
+}
+
+impl TxsBuilder {
+    /// Creates a builder with the default `Config`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether transactions for unregistered clients are rejected with `Error::AccountNotFound`.
+    /// See `Txs::register_client`.
+    pub fn require_registered_clients(mut self, enabled: bool) -> Self {
+        self.config.require_registered_clients = enabled;
+        self
+    }
+
+    /// Sets whether a deposit or withdrawal with a transaction ID not greater than
+    /// every previously processed one is rejected with `Error::NonMonotonicTxid`.
+    pub fn require_monotonic_txids(mut self, enabled: bool) -> Self {
+        self.config.require_monotonic_txids = enabled;
+        self
+    }
+
+    /// Sets the clients whose accounts are not locked when a chargeback occurs.
+    pub fn no_lock_clients<I: IntoIterator<Item = Cid>>(mut self, cids: I) -> Self {
+        self.config.no_lock_clients = cids.into_iter().collect();
+        self
+    }
+
+    /// Sets the rounding strategy used for amounts with more than `MAX_DECIMAL_PLACES` decimal places,
+    /// instead of rejecting them with `Error::TooManyDecimalPlaces`.
+    pub fn round_amounts(mut self, strategy: RoundingStrategy) -> Self {
+        self.config.amount_rounding = Some(strategy);
+        self
+    }
+
+    /// Sets whether a withdrawal that would leave zero available funds while some funds are held
+    /// is rejected with `Error::WouldStrandHeldFunds`.
+    pub fn block_withdrawal_to_zero_with_held(mut self, enabled: bool) -> Self {
+        self.config.block_withdrawal_to_zero_with_held = enabled;
+        self
+    }
+
+    /// Sets whether a dispute that would make the available funds negative
+    /// is rejected with `Error::InsuffienctFunds`, leaving the account untouched.
+    pub fn strict_disputes(mut self, enabled: bool) -> Self {
+        self.config.strict_disputes = enabled;
+        self
+    }
+
+    /// Sets whether zero-amount deposits and withdrawals succeed as no-ops.
+    pub fn ignore_zero_amount(mut self, enabled: bool) -> Self {
+        self.config.ignore_zero_amount = enabled;
+        self
+    }
+
+    /// Sets the number of most recent deposits of each client that can be disputed.
+    /// Disputing an older deposit is rejected with `Error::DisputeWindowExpired`.
+    pub fn disputable_window(mut self, window: usize) -> Self {
+        self.config.disputable_window = Some(window);
+        self
+    }
+
+    /// Sets whether a deposit or withdrawal reusing the ID of a charged-back transaction
+    /// is accepted, replacing it, instead of being rejected with `Error::TxAlreadyExists`.
+    pub fn allow_txid_reuse_after_chargeback(mut self, enabled: bool) -> Self {
+        self.config.allow_txid_reuse_after_chargeback = enabled;
+        self
+    }
+
+    /// Sets whether disputing a transaction that was ever disputed before,
+    /// even if its dispute was resolved, is rejected with `Error::TxAlreadyDisputed`.
+    pub fn single_dispute(mut self, enabled: bool) -> Self {
+        self.config.single_dispute = enabled;
+        self
+    }
+
+    /// Sets the overdraft limit of every client, allowing its available funds to go down to `-limit`.
+    /// Limits set with `Txs::set_overdraft_limit` take precedence.
+    pub fn overdraft_limit(mut self, limit: Decimal) -> Self {
+        self.config.overdraft_limit = limit;
+        self
+    }
+
+    /// Sets whether only disputable transactions are stored, to bound memory usage.
+    pub fn store_disputable_only(mut self, enabled: bool) -> Self {
+        self.config.store_disputable_only = enabled;
+        self
+    }
+
+    /// Sets a transformation applied to the raw amount of every deposit and withdrawal,
+    /// _e.g._, FX conversion or unit scaling.
+    /// The transformed amount is the one validated, stored, and applied to the account.
+    /// Disputes, resolves and chargebacks are unaffected.
+    ///
+    /// The transformation should be deterministic, _i.e._, always return the same amount
+    /// for the same input, so that reprocessing the same transactions yields the same balances.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rust_decimal_macros::dec;
+    ///
+    /// let mut txs = toy_payments_engine::Txs::builder()
+    ///     .amount_transform(Box::new(|amount| amount / dec!(100)))
+    ///     .build();
+    /// txs.deposit(1, 1001, dec!(250)).unwrap();
+    /// assert_eq!(txs.get(1).unwrap().available, dec!(2.5));
+    /// ```
+    pub fn amount_transform(
+        mut self,
+        transform: Box<dyn Fn(Decimal) -> Decimal + Send + Sync>,
+    ) -> Self {
+        self.config.amount_transform = Some(Arc::from(transform));
+        self
+    }
+
+    /// Creates an empty `Txs` using the configured policies.
+    pub fn build(self) -> Txs {
+        Txs::with_config(self.config)
+    }
 }
 
 /// Represents a collection of incoming transactions to be processed.
-#[derive(Debug)]
+///
+/// Cloning a `Txs` takes a snapshot of its whole state,
+/// which can be compared or resumed independently of the original.
+#[derive(Debug, Clone)]
 pub struct Txs {
     txs: HashMap<Txid, Tx>,
     accounts: HashMap<Cid, Account>,
+    seen_txids: HashSet<Txid>,
+    next_seq: u64,
+    max_txid: Option<Txid>,
+    disputes_enabled: bool,
+    overdraft_limits: HashMap<Cid, Decimal>,
+    /// The sequence numbers of the stored deposits of every client, in increasing order,
+    /// only tracked when `Config::disputable_window` is set.
+    deposit_seqs: HashMap<Cid, Vec<u64>>,
+    config: Config,
 }
 
 impl Default for Txs {
@@ -185,6 +791,39 @@ impl Default for Txs {
     }
 }
 
+impl FromIterator<Tx> for Txs {
+    /// Creates a `Txs` by processing every transaction in `iter` in order.
+    ///
+    /// Transactions that cannot be processed are skipped and logged as warnings,
+    /// as in `csv::process_transactions`.
+    /// Since transactions are processed in order, a dispute must come after its deposit.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toy_payments_engine::*;
+    /// # use rust_decimal_macros::dec;
+    /// let txs: Txs = vec![
+    ///     Tx::deposit(1, 1001, dec!(10)),
+    ///     Tx::withdrawal(1, 1002, dec!(4)),
+    /// ]
+    /// .into_iter()
+    /// .collect();
+    ///
+    /// assert_eq!(txs.get(1).unwrap().available, dec!(6));
+    /// ```
+    fn from_iter<I: IntoIterator<Item = Tx>>(iter: I) -> Self {
+        let mut txs = Txs::new();
+        for tx in iter {
+            let txid = tx.txid;
+            if let Err(err) = txs.process_tx(tx) {
+                warn!("Warning in transaction {}: {}", txid, err);
+            }
+        }
+        txs
+    }
+}
+
 impl Txs {
     /// Creates an empty `Txs`.
     ///
@@ -197,383 +836,3112 @@ impl Txs {
     /// let txs = toy_payments_engine::Txs::new();
     /// ```
     pub fn new() -> Self {
-        Self {
-            txs: HashMap::new(),
-            accounts: HashMap::new(),
-        }
-    }
-
-    /// Returns an account if exists, otherwise `None`.
-    pub fn get(&self, cid: Cid) -> Option<&Account> {
-        self.accounts.get(&cid)
+        Self::with_config(Config::default())
     }
 
-    /// Processes an incoming `Deposit` transaction.
-    /// 
-    /// The amount must be a positive value.
+    /// Creates an empty `Txs` with pre-allocated space for at least `txs` transactions
+    /// and `accounts` client accounts.
+    ///
+    /// This is a performance-only helper to avoid rehashing when processing large inputs;
+    /// the resulting `Txs` behaves identically to one created with `Txs::new`.
     ///
     /// # Examples
     ///
     /// ```
-    /// # use toy_payments_engine::*;
-    /// # use rust_decimal_macros::dec;
-    /// let mut txs = Txs::new();
-    /// txs.deposit(1, 1001, dec!(15.005)).unwrap();
-    /// txs.deposit(1, 1002, dec!(24.996)).unwrap();
-    /// txs.deposit(2, 1003, dec!(30)).unwrap();
-    /// txs.deposit(3, 1004, dec!(5)).unwrap();
-    /// txs.deposit(1, 1005, dec!(5.2)).unwrap();
-    ///
-    /// assert_eq!(txs.get(1).unwrap().available, dec!(45.201));
-    /// assert_eq!(txs.get(2).unwrap().available, dec!(30));
-    /// assert_eq!(txs.get(3).unwrap().available, dec!(5));
+    /// let txs = toy_payments_engine::Txs::with_capacity(1_000_000, 10_000);
     /// ```
+    pub fn with_capacity(txs: usize, accounts: usize) -> Self {
+        let mut result = Self::new();
+        result.txs.reserve(txs);
+        result.accounts.reserve(accounts);
+        result
+    }
+
+    /// Removes every transaction and account, keeping the allocated capacity
+    /// and the policies this `Txs` was built with.
+    ///
+    /// Afterwards, this `Txs` behaves as a newly created one,
+    /// _e.g._, disputes are enabled and no overdraft limits are set.
+    ///
+    /// # Examples
     ///
-    /// The deposit fails if the amount to deposit is not positive.
-    /// 
     /// ```
     /// # use toy_payments_engine::*;
     /// # use rust_decimal_macros::dec;
     /// let mut txs = Txs::new();
-    /// assert_eq!(txs.deposit(1, 1001, dec!(0)), Err(Error::InvalidAmount));
-    /// assert_eq!(txs.deposit(2, 1001, dec!(-10)), Err(Error::InvalidAmount));
+    /// txs.deposit(1, 1001, dec!(10)).unwrap();
+    /// txs.clear();
+    /// assert_eq!(txs.get(1), None);
     /// ```
-    /// 
-    /// The same transaction id cannot be used twice,
-    /// even if the client ID if different.
+    pub fn clear(&mut self) {
+        self.txs.clear();
+        self.accounts.clear();
+        self.seen_txids.clear();
+        self.next_seq = 0;
+        self.max_txid = None;
+        self.disputes_enabled = true;
+        self.overdraft_limits.clear();
+        self.deposit_seqs.clear();
+    }
+
+    /// Creates a `TxsBuilder` to configure the policies of a new `Txs`.
+    pub fn builder() -> TxsBuilder {
+        TxsBuilder::new()
+    }
+
+    fn with_config(config: Config) -> Self {
+        Self {
+            txs: HashMap::new(),
+            accounts: HashMap::new(),
+            seen_txids: HashSet::new(),
+            next_seq: 0,
+            max_txid: None,
+            disputes_enabled: true,
+            overdraft_limits: HashMap::new(),
+            deposit_seqs: HashMap::new(),
+            config,
+        }
+    }
+
+    /// Sets whether new disputes are accepted, _e.g._, to pause them during a maintenance window.
+    ///
+    /// While disabled, every dispute is rejected with `Error::DisputesDisabled`.
+    /// Deposits and withdrawals are still processed,
+    /// and existing disputes can still be resolved or charged back.
+    ///
+    /// # Examples
     ///
     /// ```
     /// # use toy_payments_engine::*;
     /// # use rust_decimal_macros::dec;
     /// let mut txs = Txs::new();
     /// txs.deposit(1, 1001, dec!(10)).unwrap();
-    ///
-    /// assert_eq!(txs.deposit(1, 1001, dec!(15)), Err(Error::TxAlreadyExists));
-    /// assert_eq!(txs.deposit(2, 1001, dec!(15)), Err(Error::TxAlreadyExists));
+    /// txs.set_disputes_enabled(false);
+    /// assert_eq!(txs.dispute(1, 1001), Err(Error::DisputesDisabled));
+    /// txs.set_disputes_enabled(true);
+    /// assert_eq!(txs.dispute(1, 1001), Ok(()));
     /// ```
+    pub fn set_disputes_enabled(&mut self, enabled: bool) {
+        self.disputes_enabled = enabled;
+    }
+
+    /// Sets the overdraft limit of client `cid`, _e.g._, for a credit line,
+    /// allowing its available funds to go down to `-limit`.
     ///
-    /// The transaction processing fails when the `available` amount in the account overflows.
+    /// Clients without their own limit use `Config::overdraft_limit`,
+    /// which is zero by default, _i.e._, no overdraft is allowed.
+    /// Operations that would take the available funds below `-limit`
+    /// are rejected with `Error::InsuffienctFunds`.
+    ///
+    /// # Examples
     ///
     /// ```
     /// # use toy_payments_engine::*;
-    /// # use rust_decimal::Decimal;
     /// # use rust_decimal_macros::dec;
     /// let mut txs = Txs::new();
-    /// txs.deposit(1, 1001, Decimal::MAX).unwrap();
-    /// 
-    /// assert_eq!(txs.deposit(1, 1002, dec!(1)), Err(Error::MathError));
+    /// txs.deposit(1, 1001, dec!(10)).unwrap();
+    /// txs.set_overdraft_limit(1, dec!(5));
+    ///
+    /// txs.withdrawal(1, 1002, dec!(15)).unwrap();
+    /// assert_eq!(txs.get(1).unwrap().available, dec!(-5));
     /// ```
-    pub fn deposit(&mut self, cid: Cid, txid: Txid, amount: Decimal) -> Result<(), Error> {
-        self.process_tx(Tx::deposit(cid, txid, amount))
+    pub fn set_overdraft_limit(&mut self, cid: Cid, limit: Decimal) {
+        self.overdraft_limits.insert(cid, limit);
     }
 
-    /// Processes an incoming `Withdrawal` transaction.
-    /// The amount must be a positive value.
+    /// Returns the policies used by this `Txs` to process transactions.
     ///
     /// # Examples
     ///
     /// ```
-    /// use toy_payments_engine::*;
-    /// use rust_decimal_macros::dec;
+    /// # use toy_payments_engine::*;
+    /// let txs = Txs::builder().require_registered_clients(true).build();
+    /// assert!(txs.config().require_registered_clients);
+    /// ```
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Returns an account if exists, otherwise `None`.
+    pub fn get(&self, cid: Cid) -> Option<&Account> {
+        self.accounts.get(&cid)
+    }
+
+    /// Returns whether transaction `txid` is currently disputed,
+    /// or `Error::TxNotFound` if there is no such stored transaction.
+    ///
+    /// # Examples
     ///
+    /// ```
+    /// # use toy_payments_engine::*;
+    /// # use rust_decimal_macros::dec;
     /// let mut txs = Txs::new();
-    /// txs.deposit(1, 1001, dec!(15.005)).unwrap();
-    /// txs.deposit(1, 1002, dec!(24.996)).unwrap();
+    /// txs.deposit(1, 1001, dec!(10)).unwrap();
+    /// txs.dispute(1, 1001).unwrap();
     ///
-    /// txs.deposit(2, 1003, dec!(10)).unwrap();
+    /// assert_eq!(txs.is_disputed(1001), Ok(true));
+    /// assert_eq!(txs.is_disputed(1002), Err(Error::TxNotFound));
+    /// ```
+    pub fn is_disputed(&self, txid: Txid) -> Result<bool, Error> {
+        self.txs
+            .get(&txid)
+            .map(|tx| tx.state == DisputeState::Disputed)
+            .ok_or(Error::TxNotFound)
+    }
+
+    /// Returns an iterator over every currently disputed transaction,
+    /// yielding its transaction ID, client ID and amount, in no particular order.
     ///
-    /// assert_eq!(txs.get(1).unwrap().available, dec!(40.001));
-    /// assert_eq!(txs.get(2).unwrap().available, dec!(10));
+    /// # Examples
     ///
-    /// txs.withdrawal(1, 1004, dec!(10.002)).unwrap();
-    /// txs.withdrawal(2, 1005, dec!(10)).unwrap();
+    /// ```
+    /// # use toy_payments_engine::*;
+    /// # use rust_decimal_macros::dec;
+    /// let mut txs = Txs::new();
+    /// txs.deposit(1, 1001, dec!(10)).unwrap();
+    /// txs.deposit(2, 1002, dec!(5)).unwrap();
+    /// txs.dispute(2, 1002).unwrap();
     ///
-    /// assert_eq!(txs.get(1).unwrap().available, dec!(29.999));
-    /// assert_eq!(txs.get(2).unwrap().available, dec!(0));
+    /// let disputes: Vec<_> = txs.disputed_txs().collect();
+    /// assert_eq!(disputes, vec![(1002, 2, dec!(5))]);
     /// ```
+    pub fn disputed_txs(&self) -> impl Iterator<Item = (Txid, Cid, Decimal)> + '_ {
+        self.txs
+            .values()
+            .filter(|tx| tx.state == DisputeState::Disputed)
+            .map(|tx| (tx.txid, tx.cid, tx.amount.unwrap_or_default()))
+    }
+
+    /// Returns the `available`, `held` and total funds of client `cid`.
     ///
-    /// The processing fails when funds are not sufficient for withdrawal.
+    /// Returns `Error::AccountNotFound` if the client has no account,
+    /// or `Error::MathError` if its total funds overflow.
+    ///
+    /// # Examples
     ///
     /// ```
     /// # use toy_payments_engine::*;
     /// # use rust_decimal_macros::dec;
-    ///
     /// let mut txs = Txs::new();
+    /// txs.deposit(1, 1001, dec!(10)).unwrap();
+    /// txs.deposit(1, 1002, dec!(2.5)).unwrap();
+    /// txs.dispute(1, 1002).unwrap();
     ///
-    /// assert_eq!( txs.withdrawal(1, 1001, dec!(1)), Err(Error::InsuffienctFunds) );
+    /// assert_eq!(txs.balance(1), Ok((dec!(10), dec!(2.5), dec!(12.5))));
+    /// assert_eq!(txs.balance(2), Err(Error::AccountNotFound));
     /// ```
-    pub fn withdrawal(&mut self, cid: Cid, txid: Txid, amount: Decimal) -> Result<(), Error> {
-        self.process_tx(Tx::withdrawal(cid, txid, amount))
+    pub fn balance(&self, cid: Cid) -> Result<(Decimal, Decimal, Decimal), Error> {
+        let account = self.get(cid).ok_or(Error::AccountNotFound)?;
+        let total = account.total().ok_or(Error::MathError)?;
+        Ok((account.available, account.held, total))
     }
 
-    /// Processes an incoming `Dispute` transaction.
+    /// Rounds the `available` and `held` funds of every account to `scale` decimal places.
+    ///
+    /// Rounding uses the banker's rounding strategy of `Decimal::round_dp`.
+    /// Note that this permanently loses any precision below `scale` in the engine itself,
+    /// not only in the output.
     ///
     /// # Examples
     ///
     /// ```
     /// # use toy_payments_engine::*;
     /// # use rust_decimal_macros::dec;
-    ///
     /// let mut txs = Txs::new();
+    /// txs.deposit(1, 1001, dec!(10.1256)).unwrap();
     ///
-    /// txs.deposit(1, 1001, dec!(20)).unwrap();
-    /// txs.deposit(1, 1002, dec!(10)).unwrap();
-    /// txs.dispute(1, 1001).unwrap();
-    ///
-    /// assert_eq!(txs.get(1), Some(&Account::new(dec!(10), dec!(20), false)));
+    /// txs.round_balances(2);
+    /// assert_eq!(txs.get(1).unwrap().available, dec!(10.13));
     /// ```
+    pub fn round_balances(&mut self, scale: u32) {
+        for account in self.accounts.values_mut() {
+            account.available = account.available.round_dp(scale);
+            account.held = account.held.round_dp(scale);
+        }
+    }
+
+    /// Accrues interest on held funds, adding `held * rate` to the available funds
+    /// of every unlocked account, _e.g._, periodically over a billing period.
     ///
-    /// A second `dispute` on the same TX id will be rejected.
-    /// The state of the previous dispute is preserved.
+    /// Locked accounts are skipped.
+    /// If the interest of any account overflows, no account is updated
+    /// and `Error::MathError` is returned.
+    ///
+    /// # Examples
     ///
     /// ```
     /// # use toy_payments_engine::*;
     /// # use rust_decimal_macros::dec;
-    ///
     /// let mut txs = Txs::new();
-    ///
-    /// txs.deposit(1, 1001, dec!(20)).unwrap();
-    /// txs.deposit(1, 1002, dec!(10)).unwrap();
+    /// txs.deposit(1, 1001, dec!(100)).unwrap();
     /// txs.dispute(1, 1001).unwrap();
     ///
-    /// assert_eq!(txs.dispute(1, 1001), Err(Error::TxAlreadyDisputed));
-    ///
-    /// assert_eq!(txs.get(1), Some(&Account::new(dec!(10), dec!(20), false)));
+    /// txs.accrue_interest(dec!(0.01)).unwrap();
+    /// assert_eq!(txs.get(1), Some(&Account::new(dec!(1), dec!(100), false)));
     /// ```
+    pub fn accrue_interest(&mut self, rate: Decimal) -> Result<(), Error> {
+        let mut accrued = Vec::new();
+        for (cid, account) in self.accounts.iter().filter(|(_, account)| !account.locked) {
+            let available = account
+                .held
+                .checked_mul(rate)
+                .and_then(|interest| account.available.checked_add(interest))
+                .filter(|available| available.checked_add(account.held).is_some())
+                .ok_or(Error::MathError)?;
+            accrued.push((*cid, available));
+        }
+
+        for (cid, available) in accrued {
+            self.accounts.get_mut(&cid).unwrap().available = available;
+        }
+        Ok(())
+    }
+
+    /// Consolidates the account of client `from` into the account of client `into`.
+    ///
+    /// The available and held funds of `from` are added into `into`,
+    /// and `into` is locked if either account was locked.
+    /// Every transaction of `from` is reassigned to `into`,
+    /// so later disputes on them must reference the `into` client.
+    /// If either client has its own overdraft limit,
+    /// `into` keeps the larger of both limits, so the merged balance stays within it.
+    /// Finally, the account and overdraft limit of `from` are removed.
+    ///
+    /// # Examples
     ///
     /// ```
     /// # use toy_payments_engine::*;
     /// # use rust_decimal_macros::dec;
-    ///
     /// let mut txs = Txs::new();
+    /// txs.deposit(1, 1001, dec!(10)).unwrap();
+    /// txs.deposit(2, 1002, dec!(5)).unwrap();
     ///
-    /// txs.deposit(1, 1001, dec!(30)).unwrap();
-    /// txs.withdrawal(1, 1002, dec!(10)).unwrap();
-    /// assert_eq!(txs.dispute(1, 1002), Err(Error::TxMustBeDeposit));
+    /// txs.consolidate(2, 1).unwrap();
+    /// assert_eq!(txs.get(1), Some(&Account::new(dec!(15), dec!(0), false)));
+    /// assert_eq!(txs.get(2), None);
     /// ```
-    pub fn dispute(&mut self, cid: Cid, txid: Txid) -> Result<(), Error> {
-        self.process_tx(Tx::dispute(cid, txid))
+    pub fn consolidate(&mut self, from: Cid, into: Cid) -> Result<(), Error> {
+        if from == into {
+            return if self.accounts.contains_key(&from) {
+                Ok(())
+            } else {
+                Err(Error::AccountNotFound)
+            };
+        }
+
+        let from_account = self.accounts.get(&from).ok_or(Error::AccountNotFound)?;
+        let into_account = self.accounts.get(&into).ok_or(Error::AccountNotFound)?;
+
+        let available = into_account
+            .available
+            .checked_add(from_account.available)
+            .ok_or(Error::MathError)?;
+        let held = into_account
+            .held
+            .checked_add(from_account.held)
+            .ok_or(Error::MathError)?;
+        available.checked_add(held).ok_or(Error::MathError)?;
+        let locked = into_account.locked || from_account.locked;
+
+        if self.overdraft_limits.contains_key(&from) || self.overdraft_limits.contains_key(&into) {
+            let limit = self.overdraft_limit(from).max(self.overdraft_limit(into));
+            self.overdraft_limits.remove(&from);
+            self.overdraft_limits.insert(into, limit);
+        }
+        self.accounts.remove(&from);
+        self.accounts
+            .insert(into, Account::new(available, held, locked));
+        for tx in self.txs.values_mut().filter(|tx| tx.cid == from) {
+            tx.cid = into;
+        }
+        if let Some(seqs) = self.deposit_seqs.remove(&from) {
+            let into_seqs = self.deposit_seqs.entry(into).or_default();
+            into_seqs.extend(seqs);
+            into_seqs.sort_unstable();
+        }
+
+        Ok(())
     }
 
-    /// Processes an incoming `Resolve` transaction.
+    /// Registers a client, creating an empty account for it if it does not exist.
+    ///
+    /// Registration is only required when the `Txs` was built with
+    /// `TxsBuilder::require_registered_clients`.
     ///
     /// # Examples
     ///
     /// ```
     /// # use toy_payments_engine::*;
     /// # use rust_decimal_macros::dec;
+    /// let mut txs = Txs::builder().require_registered_clients(true).build();
     ///
-    /// let mut txs = Txs::new();
+    /// assert_eq!(txs.deposit(1, 1001, dec!(10)), Err(Error::AccountNotFound));
     ///
-    /// txs.deposit(1, 1001, dec!(20)).unwrap();
-    /// txs.deposit(1, 1002, dec!(10)).unwrap();
+    /// txs.register_client(1);
+    /// txs.deposit(1, 1001, dec!(10)).unwrap();
+    /// assert_eq!(txs.get(1).unwrap().available, dec!(10));
+    /// ```
+    pub fn register_client(&mut self, cid: Cid) {
+        self.accounts.entry(cid).or_default();
+    }
+
+    /// Unlocks the account of client `cid`, _e.g._, after a chargeback was cleared,
+    /// so that the client can deposit and withdraw again.
+    ///
+    /// Returns `Error::AccountNotFound` if the client has no account.
+    ///
+    /// # Examples
     ///
+    /// ```
+    /// # use toy_payments_engine::*;
+    /// # use rust_decimal_macros::dec;
+    /// let mut txs = Txs::new();
+    /// txs.deposit(1, 1001, dec!(10)).unwrap();
     /// txs.dispute(1, 1001).unwrap();
-    /// assert_eq!(txs.get(1), Some(&Account::new(dec!(10), dec!(20), false)));
+    /// txs.charge_back(1, 1001).unwrap();
+    /// assert!(txs.get(1).unwrap().locked);
     ///
-    /// txs.resolve(1, 1001).unwrap();
-    /// assert_eq!(txs.get(1), Some(&Account::new(dec!(30), dec!(0), false)));
+    /// txs.unlock(1).unwrap();
+    /// assert!(!txs.get(1).unwrap().locked);
+    /// assert_eq!(txs.unlock(2), Err(Error::AccountNotFound));
     /// ```
-    pub fn resolve(&mut self, cid: Cid, txid: Txid) -> Result<(), Error> {
-        self.process_tx(Tx::resolve(cid, txid))
+    pub fn unlock(&mut self, cid: Cid) -> Result<(), Error> {
+        let account = self.accounts.get_mut(&cid).ok_or(Error::AccountNotFound)?;
+        account.locked = false;
+        Ok(())
     }
 
-    /// Processes an incoming `ChargeBack` transaction.
+    /// Returns an iterator over every client's account, along with its client ID.
+    ///
+    /// Each client is yielded exactly once, in no particular order.
     ///
     /// # Examples
     ///
     /// ```
     /// # use toy_payments_engine::*;
     /// # use rust_decimal_macros::dec;
-    ///
     /// let mut txs = Txs::new();
+    /// txs.deposit(1, 1001, dec!(10)).unwrap();
+    /// txs.deposit(2, 1002, dec!(20)).unwrap();
     ///
-    /// txs.deposit(1, 1001, dec!(20)).unwrap();
-    /// txs.deposit(1, 1002, dec!(10)).unwrap();
+    /// let mut cids: Vec<_> = txs.accounts().map(|(cid, _)| cid).collect();
+    /// cids.sort();
+    /// assert_eq!(cids, vec![1, 2]);
+    /// ```
+    pub fn accounts(&self) -> impl Iterator<Item = (Cid, &Account)> {
+        self.accounts.iter().map(|(cid, account)| (*cid, account))
+    }
+
+    /// Returns the number of client accounts.
+    pub fn account_count(&self) -> usize {
+        self.accounts.len()
+    }
+
+    /// Returns the number of stored transactions, _i.e._, deposits and withdrawals.
+    ///
+    /// Disputes, resolves and chargebacks update the transaction they refer to
+    /// and are not counted.
     ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toy_payments_engine::*;
+    /// # use rust_decimal_macros::dec;
+    /// let mut txs = Txs::new();
+    /// txs.deposit(1, 1001, dec!(10)).unwrap();
     /// txs.dispute(1, 1001).unwrap();
-    /// assert_eq!(txs.get(1), Some(&Account::new(dec!(10), dec!(20), false)));
     ///
-    /// txs.charge_back(1, 1001).unwrap();
-    /// assert_eq!(txs.get(1), Some(&Account::new(dec!(10), dec!(0), true)));
+    /// assert_eq!(txs.account_count(), 1);
+    /// assert_eq!(txs.transaction_count(), 1);
     /// ```
-    pub fn charge_back(&mut self, cid: Cid, txid: Txid) -> Result<(), Error> {
-        self.process_tx(Tx::charge_back(cid, txid))
+    pub fn transaction_count(&self) -> usize {
+        self.txs.len()
     }
 
-    /// Process a transaction.
+    /// Whether there are no accounts nor stored transactions.
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty() && self.txs.is_empty()
+    }
+
+    /// Takes a snapshot of the current state of every account.
     ///
     /// # Examples
     ///
     /// ```
     /// # use toy_payments_engine::*;
-    /// # use rust_decimal_macros::*;
-    ///
+    /// # use rust_decimal_macros::dec;
     /// let mut txs = Txs::new();
+    /// txs.deposit(1, 1001, dec!(10)).unwrap();
     ///
-    /// txs.process_tx(Tx::deposit(1, 1000, dec!(10))).unwrap();
-    /// assert_eq!(txs.get(1).unwrap().available, dec!(10) );
+    /// let snapshot = txs.engine_snapshot();
+    /// txs.deposit(1, 1002, dec!(5)).unwrap();
+    ///
+    /// assert_eq!(snapshot.get(1).unwrap().available, dec!(10));
+    /// assert!(snapshot.get(1).unwrap().differs_from(txs.get(1).unwrap()));
     /// ```
-    pub fn process_tx(&mut self, tx: Tx) -> Result<(), Error> {
-        if self
-            .accounts
-            .get(&tx.cid)
-            .map_or(false, |account| account.locked)
-        {
-            return Err(Error::AccountIsLocked);
-        }
-
-        match (tx.kind, tx.amount) {
-            (TxKind::Deposit, Some(amount)) => {
-                self.process_operation(tx, amount, Decimal::checked_add)
-            }
-            (TxKind::Withdrawal, Some(amount)) => {
-                self.process_operation(tx, amount, Decimal::checked_sub)
-            }
-            (TxKind::Dispute, None) => self.with_tx(tx, |ref_tx, account| {
-                if !ref_tx.disputed {
-                    if ref_tx.kind == TxKind::Deposit {
-                        account.available -= ref_tx.amount.unwrap();
-                        account.held += ref_tx.amount.unwrap();
-                        ref_tx.disputed = true;
-                        Ok(())
-                    } else {
-                        Err(Error::TxMustBeDeposit)
-                    }
-                } else {
-                    Err(Error::TxAlreadyDisputed)
-                }
-            }),
-            (TxKind::Resolve, None) => self.with_tx(tx, |ref_tx, account| {
-                if ref_tx.disputed {
-                    account.available += ref_tx.amount.unwrap();
-                    account.held -= ref_tx.amount.unwrap();
-                    ref_tx.disputed = false;
-                    Ok(())
-                } else {
-                    Err(Error::TxNotDisputed)
-                }
-            }),
-            (TxKind::ChargeBack, None) => self.with_tx(tx, |ref_tx, account| {
-                if ref_tx.disputed {
-                    account.held -= ref_tx.amount.unwrap();
-                    account.locked = true;
-                    ref_tx.disputed = false;
-                    Ok(())
-                } else {
-                    Err(Error::TxNotDisputed)
-                }
-            }),
-            _ => Err(Error::InvalidTx),
+    pub fn engine_snapshot(&self) -> EngineSnapshot {
+        EngineSnapshot {
+            accounts: self
+                .accounts()
+                .map(|(cid, account)| (cid, AccountSnapshot::new(cid, account)))
+                .collect(),
         }
     }
 
-    fn process_operation<F: FnOnce(Decimal, Decimal) -> Option<Decimal>>(
-        &mut self,
-        tx: Tx,
-        amount: Decimal,
-        checked_op: F,
-    ) -> Result<(), Error> {
-        if amount <= Decimal::ZERO {
-            return Err(Error::InvalidAmount);
-        }
+    /// Takes a serializable snapshot of the whole state of this `Txs`,
+    /// including the dispute state of every stored transaction.
+    ///
+    /// The policies in `Config` are not part of the snapshot.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toy_payments_engine::*;
+    /// # use rust_decimal_macros::dec;
+    /// let mut txs = Txs::new();
+    /// txs.deposit(1, 1001, dec!(10)).unwrap();
+    ///
+    /// let json = serde_json::to_string(&txs.snapshot()).unwrap();
+    /// let mut txs = Txs::restore(serde_json::from_str(&json).unwrap());
+    ///
+    /// txs.dispute(1, 1001).unwrap();
+    /// assert_eq!(txs.get(1), Some(&Account::new(dec!(0), dec!(10), false)));
+    /// ```
+    pub fn snapshot(&self) -> StateSnapshot {
+        let mut accounts: Vec<(Cid, Account)> = self
+            .accounts()
+            .map(|(cid, account)| (cid, account.clone()))
+            .collect();
+        accounts.sort_by_key(|(cid, _)| *cid);
+
+        let mut txs: Vec<StoredTx> = self
+            .txs
+            .values()
+            .map(|tx| StoredTx {
+                kind: tx.kind,
+                client: tx.cid,
+                tx: tx.txid,
+                amount: tx.amount,
+                state: tx.state,
+                held: tx.held,
+                charged_back: tx.charged_back,
+                seq: tx.seq,
+            })
+            .collect();
+        txs.sort_by_key(|tx| tx.seq);
 
-        let account = self.accounts.entry(tx.cid).or_default();
+        let mut seen_txids: Vec<Txid> = self.seen_txids.iter().copied().collect();
+        seen_txids.sort();
 
-        if let Some(new_available) = checked_op(account.available, amount) {
-            if new_available < Decimal::ZERO {
-                Err(Error::InsuffienctFunds)
-            } else if let Entry::Vacant(entry) = self.txs.entry(tx.txid) {
-                if Decimal::checked_add(new_available, account.held).is_some() {
-                    entry.insert(tx);
-                    account.available = new_available;
-                    Ok(())
-                } else {
-                    Err(Error::MathError)
-                }
-            } else {
-                Err(Error::TxAlreadyExists)
-            }
-        } else {
-            Err(Error::MathError)
+        let mut overdraft_limits: Vec<(Cid, Decimal)> = self
+            .overdraft_limits
+            .iter()
+            .map(|(cid, limit)| (*cid, *limit))
+            .collect();
+        overdraft_limits.sort_by_key(|(cid, _)| *cid);
+
+        StateSnapshot {
+            accounts,
+            txs,
+            seen_txids,
+            next_seq: self.next_seq,
+            max_txid: self.max_txid,
+            disputes_enabled: self.disputes_enabled,
+            overdraft_limits,
         }
     }
 
-    fn with_tx<F: FnOnce(&mut Tx, &mut Account) -> Result<(), Error>>(
-        &mut self,
-        tx: Tx,
-        op: F,
-    ) -> Result<(), Error> {
-        let account = self.accounts.entry(tx.cid).or_default();
-        self.txs
-            .get_mut(&tx.txid)
-            .ok_or(Error::TxNotFound)
-            .and_then(|ref_tx| {
-                if ref_tx.cid == tx.cid {
-                    op(ref_tx, account)
-                } else {
-                    Err(Error::CidMismatch)
-                }
+    /// Creates a `Txs` with the default policies from a `snapshot`
+    /// taken with `Txs::snapshot`.
+    ///
+    /// Transactions stored before the snapshot was taken can still be disputed,
+    /// resolved and charged back.
+    pub fn restore(snapshot: StateSnapshot) -> Self {
+        let mut txs = Txs::new();
+        txs.accounts = snapshot.accounts.into_iter().collect();
+        txs.txs = snapshot
+            .txs
+            .into_iter()
+            .map(|tx| {
+                let mut stored = Tx::new(tx.kind, tx.client, tx.tx, tx.amount);
+                stored.state = tx.state;
+                stored.held = tx.held;
+                stored.charged_back = tx.charged_back;
+                stored.seq = tx.seq;
+                (tx.tx, stored)
             })
+            .collect();
+        txs.seen_txids = snapshot.seen_txids.into_iter().collect();
+        txs.next_seq = snapshot.next_seq;
+        txs.max_txid = snapshot.max_txid;
+        txs.disputes_enabled = snapshot.disputes_enabled;
+        txs.overdraft_limits = snapshot.overdraft_limits.into_iter().collect();
+        txs
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use rust_decimal::Decimal;
-    use rust_decimal_macros::dec;
 
-    use crate::{Error, Txs};
+    /// Verifies that the held funds of every account are fully backed by its open disputes,
+    /// _i.e._, that they equal the funds held for its disputed transactions.
+    ///
+    /// Returns the clients, sorted by client ID, whose held funds are not backed,
+    /// _e.g._, because a resolve or chargeback left stale held funds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toy_payments_engine::*;
+    /// # use rust_decimal_macros::dec;
+    /// let mut txs = Txs::new();
+    /// txs.deposit(1, 1001, dec!(10)).unwrap();
+    /// txs.dispute(1, 1001).unwrap();
+    ///
+    /// assert_eq!(txs.verify_solvency(), Ok(()));
+    /// ```
+    pub fn verify_solvency(&self) -> Result<(), Vec<Cid>> {
+        let mut backing: HashMap<Cid, Decimal> = HashMap::new();
+        for tx in self.txs.values() {
+            if tx.state == DisputeState::Disputed {
+                *backing.entry(tx.cid).or_default() += tx.held;
+            }
+        }
+
+        let mut clients: Vec<Cid> = self
+            .accounts()
+            .filter(|(cid, account)| backing.get(cid).copied().unwrap_or_default() != account.held)
+            .map(|(cid, _)| cid)
+            .collect();
+        if clients.is_empty() {
+            Ok(())
+        } else {
+            clients.sort();
+            Err(clients)
+        }
+    }
+
+    /// Computes the aggregate conservation of funds across every account,
+    /// _i.e._, whether the funds in the accounts are exactly accounted for
+    /// by the deposits, withdrawals and chargebacks that were processed.
+    ///
+    /// Balance changes not recorded as transactions, _e.g._, `accrue_interest` or `consolidate`,
+    /// and withdrawals dropped by `store_disputable_only` make the report unbalanced.
+    /// Totals that overflow are reported with `Error::MathError`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toy_payments_engine::*;
+    /// # use rust_decimal_macros::dec;
+    /// let mut txs = Txs::new();
+    /// txs.deposit(1, 1001, dec!(10)).unwrap();
+    /// txs.withdrawal(1, 1002, dec!(4)).unwrap();
+    ///
+    /// let report = txs.conservation_report().unwrap();
+    /// assert_eq!(report.available, dec!(6));
+    /// assert!(report.balanced);
+    /// ```
+    pub fn conservation_report(&self) -> Result<ConservationReport, Error> {
+        let add = |total: Decimal, amount| total.checked_add(amount).ok_or(Error::MathError);
+        let mut deposited = Decimal::ZERO;
+        let mut withdrawn = Decimal::ZERO;
+        let mut charged_back = Decimal::ZERO;
+        for tx in self.txs.values() {
+            let amount = tx.amount.unwrap_or_default();
+            match tx.kind {
+                TxKind::Deposit => {
+                    deposited = add(deposited, amount)?;
+                    charged_back = add(charged_back, tx.charged_back)?;
+                }
+                TxKind::Withdrawal => withdrawn = add(withdrawn, amount)?,
+                _ => {}
+            }
+        }
+        let mut available = Decimal::ZERO;
+        let mut held = Decimal::ZERO;
+        for account in self.accounts.values() {
+            available = add(available, account.available)?;
+            held = add(held, account.held)?;
+        }
+        let balance = add(available, held)?;
+        let net = deposited
+            .checked_sub(withdrawn)
+            .and_then(|net| net.checked_sub(charged_back))
+            .ok_or(Error::MathError)?;
+
+        Ok(ConservationReport {
+            deposited,
+            withdrawn,
+            charged_back,
+            available,
+            held,
+            balanced: balance == net,
+        })
+    }
+
+    /// Audits the internal consistency of this `Txs`,
+    /// _i.e._, that the held funds across every account equal
+    /// the funds held for every currently disputed transaction.
+    ///
+    /// Unlike `verify_solvency`, which reports the inconsistent clients,
+    /// this check is done in aggregate.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toy_payments_engine::*;
+    /// # use rust_decimal_macros::dec;
+    /// let mut txs = Txs::new();
+    /// txs.deposit(1, 1001, dec!(10)).unwrap();
+    /// txs.dispute(1, 1001).unwrap();
+    ///
+    /// assert_eq!(txs.audit(), Ok(()));
+    /// ```
+    pub fn audit(&self) -> Result<(), AuditError> {
+        let expected_held = self
+            .txs
+            .values()
+            .filter(|tx| tx.state == DisputeState::Disputed)
+            .try_fold(Decimal::ZERO, |total, tx| total.checked_add(tx.held))
+            .ok_or(AuditError::MathError)?;
+        let actual_held = self
+            .accounts
+            .values()
+            .try_fold(Decimal::ZERO, |total, account| {
+                total.checked_add(account.held)
+            })
+            .ok_or(AuditError::MathError)?;
+        if expected_held == actual_held {
+            Ok(())
+        } else {
+            Err(AuditError::HeldMismatch {
+                expected_held,
+                actual_held,
+            })
+        }
+    }
+
+    /// Overrides the held funds of client `cid`, to corrupt the state in tests.
+    #[cfg(test)]
+    fn set_held(&mut self, cid: Cid, held: Decimal) {
+        self.accounts.entry(cid).or_default().held = held;
+    }
+
+    /// Returns the clients whose held funds exceed the `threshold` fraction of their total funds,
+    /// along with the computed `held / (available + held)` ratio.
+    ///
+    /// Accounts with no total funds are never reported.
+    /// The clients are returned sorted by client ID.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toy_payments_engine::*;
+    /// # use rust_decimal_macros::dec;
+    /// let mut txs = Txs::new();
+    /// txs.deposit(1, 1001, dec!(30)).unwrap();
+    /// txs.deposit(1, 1002, dec!(10)).unwrap();
+    /// txs.dispute(1, 1001).unwrap();
+    ///
+    /// assert_eq!(txs.high_held_ratio(dec!(0.5)), vec![(1, dec!(0.75))]);
+    /// ```
+    pub fn high_held_ratio(&self, threshold: Decimal) -> Vec<(Cid, Decimal)> {
+        let mut clients: Vec<(Cid, Decimal)> = self
+            .accounts
+            .iter()
+            .filter_map(|(cid, account)| {
+                let total = account.total()?;
+                let ratio = account.held.checked_div(total)?;
+                (ratio > threshold).then_some((*cid, ratio))
+            })
+            .collect();
+        clients.sort_by_key(|(cid, _)| *cid);
+        clients
+    }
+
+    /// Returns the client with the greatest total funds, _i.e._, `available + held`,
+    /// along with that total.
+    ///
+    /// Ties are broken by the lowest client ID.
+    /// Accounts whose total overflows are ignored.
+    /// Returns `None` if there are no accounts.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toy_payments_engine::*;
+    /// # use rust_decimal_macros::dec;
+    /// let mut txs = Txs::new();
+    /// assert_eq!(txs.max_exposure(), None);
+    ///
+    /// txs.deposit(1, 1001, dec!(10)).unwrap();
+    /// txs.deposit(2, 1002, dec!(25)).unwrap();
+    /// assert_eq!(txs.max_exposure(), Some((2, dec!(25))));
+    /// ```
+    pub fn max_exposure(&self) -> Option<(Cid, Decimal)> {
+        self.accounts()
+            .filter_map(|(cid, account)| Some((cid, account.total()?)))
+            .max_by(|(cid, total), (other_cid, other_total)| {
+                total.cmp(other_total).then(other_cid.cmp(cid))
+            })
+    }
+
+    /// Processes an incoming `Deposit` transaction.
+    /// 
+    /// The amount must be a positive value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toy_payments_engine::*;
+    /// # use rust_decimal_macros::dec;
+    /// let mut txs = Txs::new();
+    /// txs.deposit(1, 1001, dec!(15.005)).unwrap();
+    /// txs.deposit(1, 1002, dec!(24.996)).unwrap();
+    /// txs.deposit(2, 1003, dec!(30)).unwrap();
+    /// txs.deposit(3, 1004, dec!(5)).unwrap();
+    /// txs.deposit(1, 1005, dec!(5.2)).unwrap();
+    ///
+    /// assert_eq!(txs.get(1).unwrap().available, dec!(45.201));
+    /// assert_eq!(txs.get(2).unwrap().available, dec!(30));
+    /// assert_eq!(txs.get(3).unwrap().available, dec!(5));
+    /// ```
+    ///
+    /// The deposit fails if the amount to deposit is not positive.
+    /// 
+    /// ```
+    /// # use toy_payments_engine::*;
+    /// # use rust_decimal_macros::dec;
+    /// let mut txs = Txs::new();
+    /// assert_eq!(txs.deposit(1, 1001, dec!(0)), Err(Error::InvalidAmount));
+    /// assert_eq!(txs.deposit(2, 1001, dec!(-10)), Err(Error::InvalidAmount));
+    /// ```
+    /// 
+    /// The same transaction id cannot be used twice,
+    /// even if the client ID if different.
+    ///
+    /// ```
+    /// # use toy_payments_engine::*;
+    /// # use rust_decimal_macros::dec;
+    /// let mut txs = Txs::new();
+    /// txs.deposit(1, 1001, dec!(10)).unwrap();
+    ///
+    /// assert_eq!(txs.deposit(1, 1001, dec!(15)), Err(Error::TxAlreadyExists));
+    /// assert_eq!(txs.deposit(2, 1001, dec!(15)), Err(Error::TxAlreadyExists));
+    /// ```
+    ///
+    /// The transaction processing fails when the `available` amount in the account overflows.
+    ///
+    /// ```
+    /// # use toy_payments_engine::*;
+    /// # use rust_decimal::Decimal;
+    /// # use rust_decimal_macros::dec;
+    /// let mut txs = Txs::new();
+    /// txs.deposit(1, 1001, Decimal::MAX).unwrap();
+    /// 
+    /// assert_eq!(txs.deposit(1, 1002, dec!(1)), Err(Error::MathError));
+    /// ```
+    pub fn deposit(&mut self, cid: Cid, txid: Txid, amount: Decimal) -> Result<(), Error> {
+        self.process_tx(Tx::deposit(cid, txid, amount))
+    }
+
+    /// Processes an incoming `Withdrawal` transaction.
+    /// The amount must be a positive value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use toy_payments_engine::*;
+    /// use rust_decimal_macros::dec;
+    ///
+    /// let mut txs = Txs::new();
+    /// txs.deposit(1, 1001, dec!(15.005)).unwrap();
+    /// txs.deposit(1, 1002, dec!(24.996)).unwrap();
+    ///
+    /// txs.deposit(2, 1003, dec!(10)).unwrap();
+    ///
+    /// assert_eq!(txs.get(1).unwrap().available, dec!(40.001));
+    /// assert_eq!(txs.get(2).unwrap().available, dec!(10));
+    ///
+    /// txs.withdrawal(1, 1004, dec!(10.002)).unwrap();
+    /// txs.withdrawal(2, 1005, dec!(10)).unwrap();
+    ///
+    /// assert_eq!(txs.get(1).unwrap().available, dec!(29.999));
+    /// assert_eq!(txs.get(2).unwrap().available, dec!(0));
+    /// ```
+    ///
+    /// The processing fails when funds are not sufficient for withdrawal.
+    ///
+    /// ```
+    /// # use toy_payments_engine::*;
+    /// # use rust_decimal_macros::dec;
+    ///
+    /// let mut txs = Txs::new();
+    ///
+    /// assert_eq!( txs.withdrawal(1, 1001, dec!(1)), Err(Error::InsuffienctFunds) );
+    /// ```
+    pub fn withdrawal(&mut self, cid: Cid, txid: Txid, amount: Decimal) -> Result<(), Error> {
+        self.process_tx(Tx::withdrawal(cid, txid, amount))
+    }
+
+    /// Processes an incoming `Dispute` transaction.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toy_payments_engine::*;
+    /// # use rust_decimal_macros::dec;
+    ///
+    /// let mut txs = Txs::new();
+    ///
+    /// txs.deposit(1, 1001, dec!(20)).unwrap();
+    /// txs.deposit(1, 1002, dec!(10)).unwrap();
+    /// txs.dispute(1, 1001).unwrap();
+    ///
+    /// assert_eq!(txs.get(1), Some(&Account::new(dec!(10), dec!(20), false)));
+    /// ```
+    ///
+    /// A second `dispute` on the same TX id will be rejected.
+    /// The state of the previous dispute is preserved.
+    ///
+    /// ```
+    /// # use toy_payments_engine::*;
+    /// # use rust_decimal_macros::dec;
+    ///
+    /// let mut txs = Txs::new();
+    ///
+    /// txs.deposit(1, 1001, dec!(20)).unwrap();
+    /// txs.deposit(1, 1002, dec!(10)).unwrap();
+    /// txs.dispute(1, 1001).unwrap();
+    ///
+    /// assert_eq!(txs.dispute(1, 1001), Err(Error::TxAlreadyDisputed));
+    ///
+    /// assert_eq!(txs.get(1), Some(&Account::new(dec!(10), dec!(20), false)));
+    /// ```
+    ///
+    /// ```
+    /// # use toy_payments_engine::*;
+    /// # use rust_decimal_macros::dec;
+    ///
+    /// let mut txs = Txs::new();
+    ///
+    /// txs.deposit(1, 1001, dec!(30)).unwrap();
+    /// txs.withdrawal(1, 1002, dec!(10)).unwrap();
+    /// assert_eq!(txs.dispute(1, 1002), Err(Error::TxMustBeDeposit));
+    /// ```
+    pub fn dispute(&mut self, cid: Cid, txid: Txid) -> Result<(), Error> {
+        self.process_tx(Tx::dispute(cid, txid))
+    }
+
+    /// Processes an incoming `Resolve` transaction.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toy_payments_engine::*;
+    /// # use rust_decimal_macros::dec;
+    ///
+    /// let mut txs = Txs::new();
+    ///
+    /// txs.deposit(1, 1001, dec!(20)).unwrap();
+    /// txs.deposit(1, 1002, dec!(10)).unwrap();
+    ///
+    /// txs.dispute(1, 1001).unwrap();
+    /// assert_eq!(txs.get(1), Some(&Account::new(dec!(10), dec!(20), false)));
+    ///
+    /// txs.resolve(1, 1001).unwrap();
+    /// assert_eq!(txs.get(1), Some(&Account::new(dec!(30), dec!(0), false)));
+    /// ```
+    pub fn resolve(&mut self, cid: Cid, txid: Txid) -> Result<(), Error> {
+        self.process_tx(Tx::resolve(cid, txid))
+    }
+
+    /// Processes an incoming `ChargeBack` transaction.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toy_payments_engine::*;
+    /// # use rust_decimal_macros::dec;
+    ///
+    /// let mut txs = Txs::new();
+    ///
+    /// txs.deposit(1, 1001, dec!(20)).unwrap();
+    /// txs.deposit(1, 1002, dec!(10)).unwrap();
+    ///
+    /// txs.dispute(1, 1001).unwrap();
+    /// assert_eq!(txs.get(1), Some(&Account::new(dec!(10), dec!(20), false)));
+    ///
+    /// txs.charge_back(1, 1001).unwrap();
+    /// assert_eq!(txs.get(1), Some(&Account::new(dec!(10), dec!(0), true)));
+    /// ```
+    pub fn charge_back(&mut self, cid: Cid, txid: Txid) -> Result<(), Error> {
+        self.process_tx(Tx::charge_back(cid, txid))
+    }
+
+    /// Processes an incoming `Freeze` transaction, locking the account of client `cid`.
+    ///
+    /// A frozen account rejects every transaction with `Error::AccountIsLocked`,
+    /// as if it was locked by a chargeback, until it is unlocked with `Txs::unlock`.
+    /// Freezing an already locked account has no effect.
+    /// Returns `Error::AccountNotFound` if the client has no account.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toy_payments_engine::*;
+    /// # use rust_decimal_macros::dec;
+    ///
+    /// let mut txs = Txs::new();
+    ///
+    /// txs.deposit(1, 1001, dec!(20)).unwrap();
+    /// txs.freeze(1).unwrap();
+    /// assert_eq!(txs.deposit(1, 1002, dec!(5)), Err(Error::AccountIsLocked));
+    ///
+    /// txs.unlock(1).unwrap();
+    /// txs.deposit(1, 1002, dec!(5)).unwrap();
+    /// ```
+    pub fn freeze(&mut self, cid: Cid) -> Result<(), Error> {
+        self.process_tx(Tx::freeze(cid))
+    }
+
+    /// Processes an incoming `Correction` transaction.
+    ///
+    /// The stored amount of the deposit `txid` is replaced by `amount`,
+    /// and the client's available funds are adjusted by the difference.
+    /// A disputed or charged back deposit cannot be corrected,
+    /// nor can a correction take the available funds below the client's overdraft limit.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toy_payments_engine::*;
+    /// # use rust_decimal_macros::dec;
+    ///
+    /// let mut txs = Txs::new();
+    ///
+    /// txs.deposit(1, 1001, dec!(20)).unwrap();
+    /// txs.correction(1, 1001, dec!(2)).unwrap();
+    /// assert_eq!(txs.get(1), Some(&Account::new(dec!(2), dec!(0), false)));
+    /// ```
+    pub fn correction(&mut self, cid: Cid, txid: Txid, amount: Decimal) -> Result<(), Error> {
+        self.process_tx(Tx::correction(cid, txid, amount))
+    }
+
+    /// Releases `amount` from the funds held across the client's open disputes.
+    ///
+    /// Disputes are resolved in FIFO order, _i.e._,
+    /// the disputed deposit that was recorded first is fully resolved before touching the next one.
+    /// A dispute that is partially released stays open with the remaining held funds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toy_payments_engine::*;
+    /// # use rust_decimal_macros::dec;
+    ///
+    /// let mut txs = Txs::new();
+    ///
+    /// txs.deposit(1, 1001, dec!(20)).unwrap();
+    /// txs.deposit(1, 1002, dec!(10)).unwrap();
+    /// txs.dispute(1, 1001).unwrap();
+    /// txs.dispute(1, 1002).unwrap();
+    ///
+    /// txs.resolve_amount(1, dec!(25)).unwrap();
+    /// assert_eq!(txs.get(1), Some(&Account::new(dec!(25), dec!(5), false)));
+    /// ```
+    ///
+    /// The release fails if `amount` exceeds the held funds,
+    /// or with `Error::AccountNotFound` if the client has no account.
+    ///
+    /// ```
+    /// # use toy_payments_engine::*;
+    /// # use rust_decimal_macros::dec;
+    ///
+    /// let mut txs = Txs::new();
+    ///
+    /// txs.deposit(1, 1001, dec!(20)).unwrap();
+    /// txs.dispute(1, 1001).unwrap();
+    ///
+    /// assert_eq!(txs.resolve_amount(1, dec!(21)), Err(Error::AmountExceedsHeld));
+    /// ```
+    pub fn resolve_amount(&mut self, cid: Cid, amount: Decimal) -> Result<(), Error> {
+        if amount <= Decimal::ZERO {
+            return Err(Error::InvalidAmount);
+        }
+
+        let account = self.accounts.get_mut(&cid).ok_or(Error::AccountNotFound)?;
+        if account.locked {
+            return Err(Error::AccountIsLocked);
+        }
+
+        let mut disputes: Vec<&mut Tx> = self
+            .txs
+            .values_mut()
+            .filter(|tx| tx.cid == cid && tx.state == DisputeState::Disputed)
+            .collect();
+
+        if disputes.iter().map(|tx| tx.held).sum::<Decimal>() < amount {
+            return Err(Error::AmountExceedsHeld);
+        }
+
+        let available = account
+            .available
+            .checked_add(amount)
+            .ok_or(Error::MathError)?;
+        let held = account.held.checked_sub(amount).ok_or(Error::MathError)?;
+        if held < Decimal::ZERO {
+            return Err(Error::HeldUnderflow);
+        }
+
+        disputes.sort_by_key(|tx| tx.seq);
+
+        let mut remaining = amount;
+        for tx in disputes {
+            if remaining == Decimal::ZERO {
+                break;
+            }
+
+            let release = remaining.min(tx.held);
+            tx.held = tx.held.checked_sub(release).ok_or(Error::MathError)?;
+            if tx.held == Decimal::ZERO {
+                tx.state = DisputeState::Resolved;
+            }
+            remaining -= release;
+        }
+        account.available = available;
+        account.held = held;
+
+        Ok(())
+    }
+
+    /// Charges back `amount` across the client's open disputes,
+    /// proportionally to the funds held by each disputed deposit.
+    ///
+    /// Each allocated portion is removed from the held funds and the account is locked.
+    /// A dispute whose held funds are fully charged back is finalized,
+    /// otherwise it stays open with the remaining held funds.
+    /// Portions are rounded down to the scale of `amount` (at least four decimal places),
+    /// and the rounding residue is allocated starting from the last dispute,
+    /// without exceeding the funds held by each,
+    /// so that the allocations sum exactly to `amount`.
+    ///
+    /// Returns the amount allocated to each disputed transaction,
+    /// in the order the deposits were recorded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toy_payments_engine::*;
+    /// # use rust_decimal_macros::dec;
+    ///
+    /// let mut txs = Txs::new();
+    ///
+    /// txs.deposit(1, 1001, dec!(30)).unwrap();
+    /// txs.deposit(1, 1002, dec!(10)).unwrap();
+    /// txs.dispute(1, 1001).unwrap();
+    /// txs.dispute(1, 1002).unwrap();
+    ///
+    /// assert_eq!(
+    ///     txs.charge_back_prorata(1, dec!(20)),
+    ///     Ok(vec![(1001, dec!(15)), (1002, dec!(5))])
+    /// );
+    /// assert_eq!(txs.get(1), Some(&Account::new(dec!(0), dec!(20), true)));
+    /// ```
+    pub fn charge_back_prorata(
+        &mut self,
+        cid: Cid,
+        amount: Decimal,
+    ) -> Result<Vec<(Txid, Decimal)>, Error> {
+        if amount <= Decimal::ZERO {
+            return Err(Error::InvalidAmount);
+        }
+
+        let account = self.accounts.get_mut(&cid).ok_or(Error::AccountNotFound)?;
+        if account.locked {
+            return Err(Error::AccountIsLocked);
+        }
+
+        let mut disputes: Vec<&mut Tx> = self
+            .txs
+            .values_mut()
+            .filter(|tx| tx.cid == cid && tx.state == DisputeState::Disputed)
+            .collect();
+
+        let total_held: Decimal = disputes.iter().map(|tx| tx.held).sum();
+        if total_held < amount {
+            return Err(Error::AmountExceedsHeld);
+        }
+
+        let held = account.held.checked_sub(amount).ok_or(Error::MathError)?;
+        if held < Decimal::ZERO {
+            return Err(Error::HeldUnderflow);
+        }
+
+        disputes.sort_by_key(|tx| tx.seq);
+
+        let scale = amount.scale().max(4);
+        let mut allocations = Vec::with_capacity(disputes.len());
+        let mut remaining = amount;
+        for tx in &disputes {
+            let portion = amount
+                .checked_mul(tx.held)
+                .and_then(|product| product.checked_div(total_held))
+                .ok_or(Error::MathError)?
+                .round_dp_with_strategy(scale, RoundingStrategy::ToZero)
+                .min(tx.held);
+            allocations.push((tx.txid, portion));
+            remaining = remaining.checked_sub(portion).ok_or(Error::MathError)?;
+        }
+        for (tx, (_, portion)) in disputes.iter().zip(allocations.iter_mut()).rev() {
+            let residue = remaining.min(tx.held - *portion);
+            *portion += residue;
+            remaining -= residue;
+        }
+
+        for (tx, (_, portion)) in disputes.into_iter().zip(&allocations) {
+            tx.held = tx.held.checked_sub(*portion).ok_or(Error::MathError)?;
+            tx.charged_back = tx
+                .charged_back
+                .checked_add(*portion)
+                .ok_or(Error::MathError)?;
+            if tx.held == Decimal::ZERO {
+                tx.state = DisputeState::ChargedBack;
+            }
+        }
+        account.held = held;
+        account.locked |= !self.config.no_lock_clients.contains(&cid);
+
+        Ok(allocations)
+    }
+
+    /// Process a transaction.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toy_payments_engine::*;
+    /// # use rust_decimal_macros::*;
+    ///
+    /// let mut txs = Txs::new();
+    ///
+    /// txs.process_tx(Tx::deposit(1, 1000, dec!(10))).unwrap();
+    /// assert_eq!(txs.get(1).unwrap().available, dec!(10) );
+    /// ```
+    pub fn process_tx(&mut self, tx: Tx) -> Result<(), Error> {
+        if let (TxKind::Freeze, None) = (tx.kind, tx.amount) {
+            let account = self
+                .accounts
+                .get_mut(&tx.cid)
+                .ok_or(Error::AccountNotFound)?;
+            account.locked = true;
+            return Ok(());
+        }
+
+        #[allow(clippy::unnecessary_map_or)]
+        if self
+            .accounts
+            .get(&tx.cid)
+            .map_or(false, |account| account.locked)
+        {
+            return Err(Error::AccountIsLocked);
+        }
+
+        if self.config.require_registered_clients && !self.accounts.contains_key(&tx.cid) {
+            return Err(Error::AccountNotFound);
+        }
+
+        match (tx.kind, tx.amount) {
+            (TxKind::Deposit | TxKind::Withdrawal, Some(amount))
+                if self.config.ignore_zero_amount && amount.is_zero() =>
+            {
+                Ok(())
+            }
+            (TxKind::Deposit, Some(amount)) => {
+                self.process_operation(tx, amount, Decimal::checked_add)
+            }
+            (TxKind::Withdrawal, Some(amount)) => {
+                self.process_operation(tx, amount, Decimal::checked_sub)
+            }
+            (TxKind::Dispute, None) if !self.disputes_enabled => Err(Error::DisputesDisabled),
+            (TxKind::Dispute, None) if self.is_outside_dispute_window(&tx) => {
+                Err(Error::DisputeWindowExpired)
+            }
+            (TxKind::Dispute, None) => {
+                let strict = self.config.strict_disputes;
+                let single = self.config.single_dispute;
+                self.with_tx(tx, |ref_tx, account| {
+                    if ref_tx.state == DisputeState::ChargedBack {
+                        Err(Error::AlreadyChargedBack)
+                    } else if single && ref_tx.state != DisputeState::Undisputed {
+                        Err(Error::TxAlreadyDisputed)
+                    } else if ref_tx.state != DisputeState::Disputed {
+                        if ref_tx.kind == TxKind::Deposit {
+                            let amount = ref_tx.amount.unwrap();
+                            if strict && account.available < amount {
+                                return Err(Error::InsuffienctFunds);
+                            }
+                            let available = account
+                                .available
+                                .checked_sub(amount)
+                                .ok_or(Error::MathError)?;
+                            let held = account.held.checked_add(amount).ok_or(Error::MathError)?;
+                            account.available = available;
+                            account.held = held;
+                            ref_tx.held = amount;
+                            ref_tx.state = DisputeState::Disputed;
+                            Ok(())
+                        } else {
+                            Err(Error::TxMustBeDeposit)
+                        }
+                    } else {
+                        Err(Error::TxAlreadyDisputed)
+                    }
+                })
+            }
+            (TxKind::Resolve, None) => self.with_tx(tx, |ref_tx, account| match ref_tx.state {
+                DisputeState::Disputed => {
+                    let held = account
+                        .held
+                        .checked_sub(ref_tx.held)
+                        .ok_or(Error::MathError)?;
+                    if held < Decimal::ZERO {
+                        return Err(Error::HeldUnderflow);
+                    }
+                    account.available = account
+                        .available
+                        .checked_add(ref_tx.held)
+                        .ok_or(Error::MathError)?;
+                    account.held = held;
+                    ref_tx.held = Decimal::ZERO;
+                    ref_tx.state = DisputeState::Resolved;
+                    Ok(())
+                }
+                DisputeState::ChargedBack => Err(Error::AlreadyChargedBack),
+                _ => Err(Error::TxNotDisputed),
+            }),
+            (TxKind::ChargeBack, None) => {
+                let lock = !self.config.no_lock_clients.contains(&tx.cid);
+                self.with_tx(tx, |ref_tx, account| match ref_tx.state {
+                    DisputeState::Disputed => {
+                        let held = account
+                            .held
+                            .checked_sub(ref_tx.held)
+                            .ok_or(Error::MathError)?;
+                        if held < Decimal::ZERO {
+                            return Err(Error::HeldUnderflow);
+                        }
+                        ref_tx.charged_back = ref_tx
+                            .charged_back
+                            .checked_add(ref_tx.held)
+                            .ok_or(Error::MathError)?;
+                        account.held = held;
+                        account.locked |= lock;
+                        ref_tx.held = Decimal::ZERO;
+                        ref_tx.state = DisputeState::ChargedBack;
+                        Ok(())
+                    }
+                    DisputeState::ChargedBack => Err(Error::AlreadyChargedBack),
+                    _ => Err(Error::TxNotDisputed),
+                })
+            }
+            (TxKind::Correction, Some(amount)) => {
+                let amount = self.ingest_amount(amount)?;
+                let overdraft_limit = self.overdraft_limit(tx.cid);
+                self.with_tx(tx, |ref_tx, account| {
+                    if ref_tx.kind != TxKind::Deposit {
+                        return Err(Error::TxMustBeDeposit);
+                    }
+                    match ref_tx.state {
+                        DisputeState::Disputed => return Err(Error::TxAlreadyDisputed),
+                        DisputeState::ChargedBack => return Err(Error::AlreadyChargedBack),
+                        _ => {}
+                    }
+                    let delta = amount
+                        .checked_sub(ref_tx.amount.unwrap())
+                        .ok_or(Error::MathError)?;
+                    let available = account
+                        .available
+                        .checked_add(delta)
+                        .ok_or(Error::MathError)?;
+                    if available < -overdraft_limit {
+                        Err(Error::InsuffienctFunds)
+                    } else if available.checked_add(account.held).is_none() {
+                        Err(Error::MathError)
+                    } else {
+                        account.available = available;
+                        ref_tx.amount = Some(amount);
+                        Ok(())
+                    }
+                })
+            }
+            _ => Err(Error::InvalidTx),
+        }
+    }
+
+    /// Process a transaction, returning whether it created the client's account.
+    ///
+    /// Returns `Ok(true)` if the client had no account before this transaction,
+    /// and `Ok(false)` if the account already existed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toy_payments_engine::*;
+    /// # use rust_decimal_macros::*;
+    ///
+    /// let mut txs = Txs::new();
+    ///
+    /// assert_eq!(txs.process_tx_tracked(Tx::deposit(1, 1000, dec!(10))), Ok(true));
+    /// assert_eq!(txs.process_tx_tracked(Tx::deposit(1, 1001, dec!(10))), Ok(false));
+    /// ```
+    pub fn process_tx_tracked(&mut self, tx: Tx) -> Result<bool, Error> {
+        let cid = tx.cid;
+        let existed = self.accounts.contains_key(&cid);
+        self.process_tx(tx)?;
+        Ok(!existed && self.accounts.contains_key(&cid))
+    }
+
+    /// Processes already-typed transaction rows,
+    /// _e.g._, rows fetched from a database rather than parsed from CSV.
+    ///
+    /// Each row is a tuple of kind, client ID, transaction ID and optional amount.
+    /// Returns the result of processing each row, along with its transaction ID.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toy_payments_engine::*;
+    /// # use rust_decimal_macros::dec;
+    ///
+    /// let mut txs = Txs::new();
+    /// let rows = vec![
+    ///     (TxKind::Deposit, 1, 1001, Some(dec!(10))),
+    ///     (TxKind::Withdrawal, 1, 1002, Some(dec!(20))),
+    /// ];
+    ///
+    /// assert_eq!(
+    ///     txs.process_rows(rows.into_iter()),
+    ///     vec![(1001, Ok(())), (1002, Err(Error::InsuffienctFunds))]
+    /// );
+    /// ```
+    pub fn process_rows<I: Iterator<Item = (TxKind, Cid, Txid, Option<Decimal>)>>(
+        &mut self,
+        rows: I,
+    ) -> Vec<(Txid, Result<(), Error>)> {
+        rows.map(|(kind, cid, txid, amount)| {
+            (txid, self.process_tx(Tx::new(kind, cid, txid, amount)))
+        })
+        .collect()
+    }
+
+    /// Simulates the effect of `tx` on the account of its client, _e.g._, to preview it,
+    /// without modifying this `Txs`.
+    ///
+    /// The transaction is processed on a clone of this `Txs`.
+    /// Returns the resulting snapshot of the client's account,
+    /// or the error that would cause the transaction to be rejected.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toy_payments_engine::*;
+    /// # use rust_decimal_macros::dec;
+    /// let mut txs = Txs::new();
+    /// txs.deposit(1, 1001, dec!(10)).unwrap();
+    ///
+    /// assert_eq!(txs.simulate(Tx::withdrawal(1, 1002, dec!(4))).unwrap().available, dec!(6));
+    /// assert_eq!(txs.get(1).unwrap().available, dec!(10));
+    /// ```
+    pub fn simulate(&self, tx: Tx) -> Result<AccountSnapshot, Error> {
+        let cid = tx.cid;
+        let mut txs = self.clone();
+        txs.process_tx(tx)?;
+        txs.get(cid)
+            .map(|account| AccountSnapshot::new(cid, account))
+            .ok_or(Error::AccountNotFound)
+    }
+
+    /// Processes every transaction in `txs`, _e.g._, from a `Vec<Tx>` or a custom source.
+    ///
+    /// Transactions that cannot be processed are skipped.
+    /// Returns the zero-based index of every skipped transaction, along with its error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toy_payments_engine::*;
+    /// # use rust_decimal_macros::dec;
+    /// let mut txs = Txs::new();
+    /// let failures = txs.process_all(vec![
+    ///     Tx::deposit(1, 1001, dec!(10)),
+    ///     Tx::withdrawal(1, 1002, dec!(20)),
+    /// ]);
+    ///
+    /// assert_eq!(failures, vec![(1, Error::InsuffienctFunds)]);
+    /// ```
+    pub fn process_all<I: IntoIterator<Item = Tx>>(&mut self, txs: I) -> Vec<(usize, Error)> {
+        txs.into_iter()
+            .enumerate()
+            .filter_map(|(index, tx)| self.process_tx(tx).err().map(|err| (index, err)))
+            .collect()
+    }
+
+    /// Processes every transaction in `iter`, capturing the state of the affected account
+    /// right after each one, _e.g._, to visualize the evolution of balances.
+    ///
+    /// Returns, along with each transaction ID, the snapshot of the client's account
+    /// or the error that caused the transaction to be rejected.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toy_payments_engine::*;
+    /// # use rust_decimal_macros::dec;
+    /// let mut txs = Txs::new();
+    /// let steps = txs.process_tracing(vec![
+    ///     Tx::deposit(1, 1001, dec!(10)),
+    ///     Tx::withdrawal(1, 1002, dec!(20)),
+    /// ]);
+    ///
+    /// assert_eq!(steps[0].1.as_ref().unwrap().available, dec!(10));
+    /// assert_eq!(steps[1], (1002, Err(Error::InsuffienctFunds)));
+    /// ```
+    pub fn process_tracing<I: IntoIterator<Item = Tx>>(
+        &mut self,
+        iter: I,
+    ) -> Vec<(Txid, Result<AccountSnapshot, Error>)> {
+        iter.into_iter()
+            .map(|tx| {
+                let (cid, txid) = (tx.cid, tx.txid);
+                let result = self.process_tx(tx).and_then(|()| {
+                    self.get(cid)
+                        .map(|account| AccountSnapshot::new(cid, account))
+                        .ok_or(Error::AccountNotFound)
+                });
+                (txid, result)
+            })
+            .collect()
+    }
+
+    /// Whether the deposit referenced by `tx` is older than
+    /// the configured window of disputable deposits of its client.
+    fn is_outside_dispute_window(&self, tx: &Tx) -> bool {
+        let (Some(window), Some(ref_tx)) = (self.config.disputable_window, self.txs.get(&tx.txid))
+        else {
+            return false;
+        };
+        if ref_tx.cid != tx.cid
+            || ref_tx.kind != TxKind::Deposit
+            || ref_tx.state == DisputeState::Disputed
+        {
+            return false;
+        }
+
+        let seqs = self
+            .deposit_seqs
+            .get(&ref_tx.cid)
+            .map_or(&[][..], Vec::as_slice);
+        let newer = seqs.len() - seqs.partition_point(|&seq| seq <= ref_tx.seq);
+        newer >= window
+    }
+
+    /// Records the stored deposit `tx` for the dispute window of its client.
+    fn track_deposit(&mut self, tx: &Tx) {
+        if self.config.disputable_window.is_some() && tx.kind == TxKind::Deposit {
+            self.deposit_seqs.entry(tx.cid).or_default().push(tx.seq);
+        }
+    }
+
+    /// Forgets the deposit `tx` once it is no longer stored, _e.g._, when its ID is reused.
+    fn untrack_deposit(&mut self, tx: &Tx) {
+        if let Some(seqs) = self.deposit_seqs.get_mut(&tx.cid) {
+            if let Ok(index) = seqs.binary_search(&tx.seq) {
+                seqs.remove(index);
+            }
+        }
+    }
+
+    /// Transforms, rounds and validates the raw `amount` of an incoming transaction
+    /// according to the configured policies.
+    fn ingest_amount(&self, amount: Decimal) -> Result<Decimal, Error> {
+        let amount = match &self.config.amount_transform {
+            Some(transform) => transform(amount),
+            None => amount,
+        };
+        let amount = if amount.normalize().scale() <= MAX_DECIMAL_PLACES {
+            amount
+        } else if let Some(strategy) = self.config.amount_rounding {
+            amount.round_dp_with_strategy(MAX_DECIMAL_PLACES, strategy)
+        } else {
+            return Err(Error::TooManyDecimalPlaces);
+        };
+
+        if amount <= Decimal::ZERO {
+            return Err(Error::InvalidAmount);
+        }
+        Ok(amount)
+    }
+
+    /// Returns the overdraft limit of client `cid`,
+    /// falling back to `Config::overdraft_limit` when none was set for it.
+    fn overdraft_limit(&self, cid: Cid) -> Decimal {
+        self.overdraft_limits
+            .get(&cid)
+            .copied()
+            .unwrap_or(self.config.overdraft_limit)
+    }
+
+    fn process_operation<F: FnOnce(Decimal, Decimal) -> Option<Decimal>>(
+        &mut self,
+        mut tx: Tx,
+        amount: Decimal,
+        checked_op: F,
+    ) -> Result<(), Error> {
+        let amount = self.ingest_amount(amount)?;
+        tx.amount = Some(amount);
+
+        if self.config.require_monotonic_txids && self.max_txid.is_some_and(|max| tx.txid <= max) {
+            return Err(Error::NonMonotonicTxid);
+        }
+
+        let overdraft_limit = self.overdraft_limit(tx.cid);
+        let (available, held) = self
+            .accounts
+            .get(&tx.cid)
+            .map_or((Decimal::ZERO, Decimal::ZERO), |account| {
+                (account.available, account.held)
+            });
+
+        if let Some(new_available) = checked_op(available, amount) {
+            if new_available < -overdraft_limit {
+                Err(Error::InsuffienctFunds)
+            } else if self.config.block_withdrawal_to_zero_with_held
+                && tx.kind == TxKind::Withdrawal
+                && new_available.is_zero()
+                && held > Decimal::ZERO
+            {
+                Err(Error::WouldStrandHeldFunds)
+            } else if self.seen_txids.contains(&tx.txid)
+                || self.txs.get(&tx.txid).is_some_and(|ref_tx| {
+                    !self.config.allow_txid_reuse_after_chargeback
+                        || ref_tx.state != DisputeState::ChargedBack
+                })
+            {
+                Err(Error::TxAlreadyExists)
+            } else if Decimal::checked_add(new_available, held).is_some() {
+                self.accounts.entry(tx.cid).or_default().available = new_available;
+                tx.seq = self.next_seq;
+                self.next_seq += 1;
+                self.max_txid = self.max_txid.max(Some(tx.txid));
+                if let Some(replaced) = self.txs.remove(&tx.txid) {
+                    self.untrack_deposit(&replaced);
+                }
+                if self.config.store_disputable_only && tx.kind != TxKind::Deposit {
+                    self.seen_txids.insert(tx.txid);
+                } else {
+                    self.track_deposit(&tx);
+                    self.txs.insert(tx.txid, tx);
+                }
+                Ok(())
+            } else {
+                Err(Error::MathError)
+            }
+        } else {
+            Err(Error::MathError)
+        }
+    }
+
+    fn with_tx<F: FnOnce(&mut Tx, &mut Account) -> Result<(), Error>>(
+        &mut self,
+        tx: Tx,
+        op: F,
+    ) -> Result<(), Error> {
+        self.txs
+            .get_mut(&tx.txid)
+            .ok_or(Error::TxNotFound)
+            .and_then(|ref_tx| {
+                if ref_tx.cid == tx.cid {
+                    let account = self
+                        .accounts
+                        .get_mut(&tx.cid)
+                        .ok_or(Error::AccountNotFound)?;
+                    op(ref_tx, account)
+                } else {
+                    Err(Error::CidMismatch)
+                }
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use rust_decimal::{Decimal, RoundingStrategy};
+    use rust_decimal_macros::dec;
+
+    use crate::{
+        Account, AccountSnapshot, AuditError, ConservationReport, DisputeState, Error, IdField, Tx,
+        TxKind, Txs,
+    };
+
+    #[test]
+    fn test_tx_not_found() {
+        let mut txs = Txs::new();
+        assert_eq!(txs.dispute(1, 1001).unwrap_err(), Error::TxNotFound);
+        assert_eq!(txs.resolve(1, 1001).unwrap_err(), Error::TxNotFound);
+        assert_eq!(txs.charge_back(1, 1001).unwrap_err(), Error::TxNotFound);
+    }
+
+    #[test]
+    fn test_tx_not_found_does_not_create_account() {
+        let mut txs = Txs::new();
+        assert_eq!(txs.dispute(99, 1), Err(Error::TxNotFound));
+        assert_eq!(txs.resolve(99, 1), Err(Error::TxNotFound));
+        assert_eq!(txs.charge_back(99, 1), Err(Error::TxNotFound));
+        assert_eq!(txs.get(99), None);
+
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        assert_eq!(txs.dispute(2, 1001), Err(Error::CidMismatch));
+        assert_eq!(txs.get(2), None);
+    }
+
+    #[test]
+    fn test_cid_mismatch() {
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+
+        assert_eq!(txs.dispute(2, 1001).unwrap_err(), Error::CidMismatch);
+        assert_eq!(txs.resolve(2, 1001).unwrap_err(), Error::CidMismatch);
+        assert_eq!(txs.charge_back(2, 1001).unwrap_err(), Error::CidMismatch);
+    }
+
+    #[test]
+    fn test_tx_not_disputed() {
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, dec!(20)).unwrap();
+        assert_eq!(txs.resolve(1, 1001).unwrap_err(), Error::TxNotDisputed);
+        assert_eq!(txs.charge_back(1, 1001).unwrap_err(), Error::TxNotDisputed);
+    }
+
+    #[test]
+    fn test_invalid_amount() {
+        let mut txs = Txs::new();
+        assert_eq!(txs.deposit(1, 1, dec!(0)), Err(Error::InvalidAmount));
+        assert_eq!(txs.deposit(1, 1, dec!(-5)), Err(Error::InvalidAmount));
+        assert_eq!(txs.withdrawal(1, 1, dec!(-5)), Err(Error::InvalidAmount));
+        assert_eq!(txs.withdrawal(1, 1, dec!(0)), Err(Error::InvalidAmount));
+        assert_eq!(txs.get(1), None);
+    }
+
+    #[test]
+    fn test_total_overflow_when_deposit() {
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, Decimal::MAX).unwrap();
+        txs.dispute(1, 1001).unwrap();
+
+        assert_eq!(txs.deposit(1, 1002, dec!(1)), Err(Error::MathError));
+    }
+
+    #[test]
+    fn test_account_locked() {
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.dispute(1, 1001).unwrap();
+        txs.charge_back(1, 1001).unwrap();
+
+        assert_eq!(txs.deposit(1, 1002, dec!(5)), Err(Error::AccountIsLocked));
+        assert_eq!(
+            txs.withdrawal(1, 1002, dec!(5)),
+            Err(Error::AccountIsLocked)
+        );
+        assert_eq!(txs.dispute(1, 1001), Err(Error::AccountIsLocked));
+        assert_eq!(txs.resolve(1, 1001), Err(Error::AccountIsLocked));
+        assert_eq!(txs.charge_back(1, 1001), Err(Error::AccountIsLocked));
+    }
+
+    #[test]
+    fn test_resolve_amount_fifo() {
+        let mut txs = Txs::new();
+        txs.deposit(1, 1003, dec!(10)).unwrap();
+        txs.deposit(1, 1001, dec!(30)).unwrap();
+        txs.deposit(1, 1002, dec!(5)).unwrap();
+        txs.dispute(1, 1001).unwrap();
+        txs.dispute(1, 1003).unwrap();
+
+        txs.resolve_amount(1, dec!(12)).unwrap();
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(17), dec!(28), false)));
+        assert_eq!(txs.txs.get(&1003).unwrap().state, DisputeState::Resolved);
+        assert_eq!(txs.txs.get(&1001).unwrap().held, dec!(28));
+
+        assert_eq!(txs.resolve(1, 1003), Err(Error::TxNotDisputed));
+        txs.resolve(1, 1001).unwrap();
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(45), dec!(0), false)));
+    }
+
+    #[test]
+    fn test_process_rows() {
+        let mut txs = Txs::new();
+        let rows = vec![
+            (TxKind::Deposit, 1, 1001, Some(dec!(10))),
+            (TxKind::Deposit, 1, 1002, Some(dec!(5))),
+            (TxKind::Dispute, 1, 1001, None),
+            (TxKind::Dispute, 1, 1003, None),
+            (TxKind::Resolve, 1, 1001, Some(dec!(1))),
+        ];
+
+        assert_eq!(
+            txs.process_rows(rows.into_iter()),
+            vec![
+                (1001, Ok(())),
+                (1002, Ok(())),
+                (1001, Ok(())),
+                (1003, Err(Error::TxNotFound)),
+                (1001, Err(Error::InvalidTx)),
+            ]
+        );
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(5), dec!(10), false)));
+    }
+
+    #[test]
+    fn test_high_held_ratio() {
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, dec!(90)).unwrap();
+        txs.deposit(1, 1002, dec!(10)).unwrap();
+        txs.dispute(1, 1001).unwrap();
+        txs.deposit(2, 1003, dec!(10)).unwrap();
+        txs.deposit(2, 1004, dec!(90)).unwrap();
+        txs.dispute(2, 1003).unwrap();
+        txs.deposit(3, 1005, dec!(10)).unwrap();
+        txs.withdrawal(3, 1006, dec!(10)).unwrap();
+
+        assert_eq!(txs.high_held_ratio(dec!(0.5)), vec![(1, dec!(0.9))]);
+    }
+
+    #[test]
+    fn test_require_registered_clients() {
+        let mut txs = Txs::builder().require_registered_clients(true).build();
+        assert_eq!(txs.deposit(1, 1001, dec!(10)), Err(Error::AccountNotFound));
+        assert_eq!(
+            txs.withdrawal(1, 1002, dec!(10)),
+            Err(Error::AccountNotFound)
+        );
+        assert_eq!(txs.dispute(1, 1001), Err(Error::AccountNotFound));
+        assert_eq!(txs.get(1), None);
+
+        txs.register_client(1);
+        assert_eq!(txs.get(1), Some(&Account::default()));
+
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(10), dec!(0), false)));
+        assert_eq!(txs.deposit(2, 1002, dec!(10)), Err(Error::AccountNotFound));
+    }
+
+    #[test]
+    fn test_require_monotonic_txids() {
+        let mut txs = Txs::builder().require_monotonic_txids(true).build();
+        txs.deposit(1, 1003, dec!(10)).unwrap();
+        txs.deposit(1, 1005, dec!(10)).unwrap();
+
+        assert_eq!(txs.deposit(1, 1004, dec!(10)), Err(Error::NonMonotonicTxid));
+        assert_eq!(
+            txs.withdrawal(1, 1005, dec!(1)),
+            Err(Error::NonMonotonicTxid)
+        );
+        txs.dispute(1, 1003).unwrap();
+        txs.withdrawal(1, 1006, dec!(1)).unwrap();
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(9), dec!(10), false)));
+
+        let mut txs = Txs::new();
+        txs.deposit(1, 1003, dec!(10)).unwrap();
+        txs.deposit(1, 1002, dec!(10)).unwrap();
+    }
+
+    #[test]
+    fn test_config() {
+        let txs = Txs::new();
+        assert!(!txs.config().require_registered_clients);
+        assert!(!txs.config().require_monotonic_txids);
+        assert_eq!(txs.config().overdraft_limit, dec!(0));
+
+        let txs = Txs::builder()
+            .require_monotonic_txids(true)
+            .overdraft_limit(dec!(25))
+            .build();
+        assert!(!txs.config().require_registered_clients);
+        assert!(txs.config().require_monotonic_txids);
+        assert_eq!(txs.config().overdraft_limit, dec!(25));
+    }
+
+    #[test]
+    fn test_round_balances() {
+        let mut txs = Txs::new();
+        txs.accounts
+            .insert(1, Account::new(dec!(2.00015), dec!(1.00005), false));
+
+        txs.round_balances(4);
+        assert_eq!(
+            txs.get(1),
+            Some(&Account::new(dec!(2.0002), dec!(1.0000), false))
+        );
+        assert_eq!(txs.get(1).unwrap().available.scale(), 4);
+    }
+
+    #[test]
+    fn test_error_display() {
+        let messages = [
+            (Error::InvalidAmount, "amount must be a positive value"),
+            (
+                Error::MathError,
+                "arithmetic overflow or underflow in account funds",
+            ),
+            (
+                Error::InsuffienctFunds,
+                "insufficient available funds for this operation",
+            ),
+            (
+                Error::TxAlreadyExists,
+                "transaction ID was already processed",
+            ),
+            (Error::TxNotFound, "transaction ID was not found"),
+            (
+                Error::CidMismatch,
+                "client ID does not match the client of the referenced transaction",
+            ),
+            (
+                Error::TxAlreadyDisputed,
+                "transaction is already being disputed",
+            ),
+            (Error::TxNotDisputed, "transaction is not being disputed"),
+            (
+                Error::AlreadyChargedBack,
+                "transaction dispute was already finalized by a chargeback",
+            ),
+            (
+                Error::TxMustBeDeposit,
+                "only deposit transactions can be disputed",
+            ),
+            (Error::AccountIsLocked, "account is locked"),
+            (Error::InvalidTx, "transaction is not well formed"),
+            (Error::AccountNotFound, "client account was not found"),
+            (
+                Error::NonMonotonicTxid,
+                "transaction ID is not greater than the previously processed ones",
+            ),
+            (
+                Error::TooManyDecimalPlaces,
+                "amount has more than 4 decimal places",
+            ),
+            (
+                Error::AmountExceedsHeld,
+                "amount exceeds the funds held for dispute",
+            ),
+            (
+                Error::IdOutOfRange(IdField::Client),
+                "client ID is out of range",
+            ),
+            (
+                Error::IdOutOfRange(IdField::Tx),
+                "transaction ID is out of range",
+            ),
+            (Error::DisputesDisabled, "disputes are disabled"),
+            (
+                Error::WouldStrandHeldFunds,
+                "withdrawal would leave only held funds in the account",
+            ),
+            (Error::HeldUnderflow, "disputed funds exceed the held funds"),
+            (
+                Error::DisputeWindowExpired,
+                "deposit is too old to be disputed",
+            ),
+        ];
+
+        for (err, message) in messages {
+            assert_eq!(err.to_string(), message);
+        }
+    }
+
+    #[test]
+    fn test_no_lock_clients() {
+        let mut txs = Txs::builder().no_lock_clients([1]).build();
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.deposit(1, 1002, dec!(5)).unwrap();
+        txs.deposit(2, 1003, dec!(10)).unwrap();
+        txs.deposit(2, 1004, dec!(5)).unwrap();
+        txs.dispute(1, 1001).unwrap();
+        txs.dispute(2, 1003).unwrap();
+
+        txs.charge_back(1, 1001).unwrap();
+        txs.charge_back(2, 1003).unwrap();
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(5), dec!(0), false)));
+        assert_eq!(txs.get(2), Some(&Account::new(dec!(5), dec!(0), true)));
+
+        txs.deposit(1, 1005, dec!(1)).unwrap();
+        assert_eq!(txs.deposit(2, 1006, dec!(1)), Err(Error::AccountIsLocked));
+    }
+
+    #[test]
+    fn test_process_tx_tracked() {
+        let mut txs = Txs::new();
+        assert_eq!(
+            txs.process_tx_tracked(Tx::deposit(1, 1001, dec!(10))),
+            Ok(true)
+        );
+        assert_eq!(
+            txs.process_tx_tracked(Tx::deposit(1, 1002, dec!(10))),
+            Ok(false)
+        );
+        assert_eq!(txs.process_tx_tracked(Tx::dispute(1, 1001)), Ok(false));
+        assert_eq!(
+            txs.process_tx_tracked(Tx::deposit(1, 1001, dec!(10))),
+            Err(Error::TxAlreadyExists)
+        );
+        assert_eq!(
+            txs.process_tx_tracked(Tx::deposit(2, 1003, dec!(10))),
+            Ok(true)
+        );
+    }
+
+    #[test]
+    fn test_accounts() {
+        let mut txs = Txs::new();
+        txs.deposit(3, 1001, dec!(30)).unwrap();
+        txs.deposit(1, 1002, dec!(10)).unwrap();
+        txs.deposit(3, 1003, dec!(5)).unwrap();
+        txs.deposit(2, 1004, dec!(20)).unwrap();
+
+        let mut accounts: Vec<_> = txs.accounts().collect();
+        accounts.sort_by_key(|(cid, _)| *cid);
+        assert_eq!(
+            accounts,
+            vec![
+                (1, &Account::new(dec!(10), dec!(0), false)),
+                (2, &Account::new(dec!(20), dec!(0), false)),
+                (3, &Account::new(dec!(35), dec!(0), false)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_charge_back_prorata() {
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.deposit(1, 1002, dec!(20)).unwrap();
+        txs.deposit(1, 1003, dec!(5)).unwrap();
+        txs.dispute(1, 1001).unwrap();
+        txs.dispute(1, 1002).unwrap();
+
+        assert_eq!(
+            txs.charge_back_prorata(1, dec!(10)),
+            Ok(vec![(1001, dec!(3.3333)), (1002, dec!(6.6667))])
+        );
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(5), dec!(20), true)));
+        assert_eq!(txs.txs.get(&1001).unwrap().held, dec!(6.6667));
+        assert_eq!(txs.txs.get(&1002).unwrap().held, dec!(13.3333));
+    }
+
+    #[test]
+    fn test_charge_back_prorata_residue() {
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, dec!(1)).unwrap();
+        txs.deposit(1, 1002, dec!(1)).unwrap();
+        txs.deposit(1, 1003, dec!(0.0001)).unwrap();
+        txs.dispute(1, 1001).unwrap();
+        txs.dispute(1, 1002).unwrap();
+        txs.dispute(1, 1003).unwrap();
+
+        assert_eq!(
+            txs.charge_back_prorata(1, dec!(2)),
+            Ok(vec![
+                (1001, dec!(0.9999)),
+                (1002, dec!(1)),
+                (1003, dec!(0.0001))
+            ])
+        );
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(0), dec!(0.0001), true)));
+        assert_eq!(txs.txs[&1001].state, DisputeState::Disputed);
+        assert_eq!(txs.txs[&1002].state, DisputeState::ChargedBack);
+        assert_eq!(txs.txs[&1003].state, DisputeState::ChargedBack);
+        assert_eq!(txs.verify_solvency(), Ok(()));
+    }
+
+    #[test]
+    fn test_held_funds_on_locked_account() {
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.deposit(1, 1002, dec!(5)).unwrap();
+        txs.dispute(1, 1001).unwrap();
+        txs.dispute(1, 1002).unwrap();
+        txs.charge_back(1, 1002).unwrap();
+
+        assert_eq!(txs.resolve_amount(1, dec!(11)), Err(Error::AccountIsLocked));
+        assert_eq!(
+            txs.charge_back_prorata(1, dec!(11)),
+            Err(Error::AccountIsLocked)
+        );
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(0), dec!(10), true)));
+    }
+
+    #[test]
+    fn test_charge_back_prorata_with_errors() {
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.dispute(1, 1001).unwrap();
+
+        assert_eq!(
+            txs.charge_back_prorata(1, dec!(0)),
+            Err(Error::InvalidAmount)
+        );
+        assert_eq!(
+            txs.charge_back_prorata(1, dec!(10.01)),
+            Err(Error::AmountExceedsHeld)
+        );
+        assert_eq!(
+            txs.charge_back_prorata(2, dec!(1)),
+            Err(Error::AccountNotFound)
+        );
+
+        assert_eq!(
+            txs.charge_back_prorata(1, dec!(10)),
+            Ok(vec![(1001, dec!(10))])
+        );
+        assert_eq!(txs.txs.get(&1001).unwrap().state, DisputeState::ChargedBack);
+    }
+
+    #[test]
+    fn test_consolidate() {
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.deposit(2, 1002, dec!(20)).unwrap();
+        txs.deposit(2, 1003, dec!(5)).unwrap();
+        txs.dispute(2, 1003).unwrap();
+
+        txs.consolidate(2, 1).unwrap();
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(30), dec!(5), false)));
+        assert_eq!(txs.get(2), None);
+
+        assert_eq!(txs.dispute(2, 1002), Err(Error::CidMismatch));
+        txs.dispute(1, 1002).unwrap();
+        txs.resolve(1, 1003).unwrap();
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(15), dec!(20), false)));
+
+        assert_eq!(txs.consolidate(2, 1), Err(Error::AccountNotFound));
+        assert_eq!(txs.consolidate(1, 3), Err(Error::AccountNotFound));
+    }
+
+    #[test]
+    fn test_consolidate_overdraft_limit() {
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.deposit(2, 1002, dec!(5)).unwrap();
+        txs.deposit(3, 1003, dec!(5)).unwrap();
+        txs.set_overdraft_limit(2, dec!(50));
+        txs.set_overdraft_limit(3, dec!(20));
+        txs.withdrawal(2, 1004, dec!(45)).unwrap();
+
+        txs.consolidate(2, 1).unwrap();
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(-30), dec!(0), false)));
+        assert_eq!(txs.overdraft_limits.get(&2), None);
+        assert_eq!(txs.overdraft_limits.get(&1), Some(&dec!(50)));
+
+        txs.consolidate(3, 1).unwrap();
+        assert_eq!(txs.overdraft_limits.get(&3), None);
+        assert_eq!(txs.overdraft_limits.get(&1), Some(&dec!(50)));
+        txs.withdrawal(1, 1005, dec!(25)).unwrap();
+        assert_eq!(
+            txs.withdrawal(1, 1006, dec!(0.01)),
+            Err(Error::InsuffienctFunds)
+        );
+    }
+
+    #[test]
+    fn test_too_many_decimal_places() {
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, dec!(1.0001)).unwrap();
+        txs.deposit(1, 1002, dec!(1.10000)).unwrap();
+        assert_eq!(
+            txs.deposit(1, 1003, dec!(1.00005)),
+            Err(Error::TooManyDecimalPlaces)
+        );
+        assert_eq!(
+            txs.withdrawal(1, 1004, dec!(0.00001)),
+            Err(Error::TooManyDecimalPlaces)
+        );
+        assert_eq!(
+            txs.get(1),
+            Some(&Account::new(dec!(2.1001), dec!(0), false))
+        );
+    }
+
+    #[test]
+    fn test_round_amounts() {
+        let mut txs = Txs::builder()
+            .round_amounts(RoundingStrategy::MidpointAwayFromZero)
+            .build();
+        txs.deposit(1, 1001, dec!(1.00005)).unwrap();
+        assert_eq!(
+            txs.withdrawal(1, 1002, dec!(0.00004)),
+            Err(Error::InvalidAmount)
+        );
+        assert_eq!(
+            txs.get(1),
+            Some(&Account::new(dec!(1.0001), dec!(0), false))
+        );
+        assert_eq!(txs.txs.get(&1001).unwrap().amount, Some(dec!(1.0001)));
+    }
+
+    #[test]
+    fn test_account_total() {
+        assert_eq!(
+            Account::new(dec!(10.5), dec!(4.25), false).total(),
+            Some(dec!(14.75))
+        );
+        assert_eq!(
+            Account::new(dec!(-3), dec!(1), true).total(),
+            Some(dec!(-2))
+        );
+        assert_eq!(Account::new(Decimal::MAX, dec!(1), false).total(), None);
+        assert_eq!(
+            Account::new(Decimal::MAX - dec!(1), dec!(1), false).total(),
+            Some(Decimal::MAX)
+        );
+    }
+
+    #[test]
+    fn test_already_charged_back() {
+        let mut txs = Txs::builder().no_lock_clients([1]).build();
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.deposit(1, 1002, dec!(5)).unwrap();
+        txs.dispute(1, 1001).unwrap();
+        txs.charge_back(1, 1001).unwrap();
+
+        assert_eq!(txs.resolve(1, 1001), Err(Error::AlreadyChargedBack));
+        assert_eq!(txs.charge_back(1, 1001), Err(Error::AlreadyChargedBack));
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(5), dec!(0), false)));
+        assert_eq!(txs.txs.get(&1001).unwrap().state, DisputeState::ChargedBack);
+
+        assert_eq!(txs.resolve(1, 1002), Err(Error::TxNotDisputed));
+    }
+
+    #[test]
+    fn test_store_disputable_only() {
+        let mut txs = Txs::builder().store_disputable_only(true).build();
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.withdrawal(1, 1002, dec!(4)).unwrap();
+        txs.deposit(1, 1003, dec!(5)).unwrap();
+        assert_eq!(txs.txs.len(), 2);
+        assert!(txs.seen_txids.contains(&1002));
+
+        assert_eq!(txs.deposit(1, 1002, dec!(1)), Err(Error::TxAlreadyExists));
+        assert_eq!(
+            txs.withdrawal(2, 1002, dec!(1)),
+            Err(Error::InsuffienctFunds)
+        );
+        assert_eq!(
+            txs.withdrawal(1, 1002, dec!(1)),
+            Err(Error::TxAlreadyExists)
+        );
+        assert_eq!(
+            txs.withdrawal(1, 1001, dec!(1)),
+            Err(Error::TxAlreadyExists)
+        );
+        assert_eq!(txs.dispute(1, 1002), Err(Error::TxNotFound));
+
+        txs.dispute(1, 1001).unwrap();
+        txs.resolve(1, 1001).unwrap();
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(11), dec!(0), false)));
+    }
+
+    #[test]
+    fn test_with_capacity() {
+        let mut txs = Txs::new();
+        let mut preallocated = Txs::with_capacity(100, 10);
+        assert!(preallocated.txs.capacity() >= 100);
+        assert!(preallocated.accounts.capacity() >= 10);
+        for txs in [&mut txs, &mut preallocated] {
+            txs.deposit(1, 1001, dec!(10)).unwrap();
+            txs.deposit(2, 1002, dec!(5)).unwrap();
+            txs.withdrawal(1, 1003, dec!(3)).unwrap();
+            txs.dispute(2, 1002).unwrap();
+        }
+        assert_eq!(txs.accounts, preallocated.accounts);
+        assert_eq!(txs.txs.len(), preallocated.txs.len());
+    }
+
+    #[test]
+    fn test_clear() {
+        let mut txs = Txs::builder().require_monotonic_txids(true).build();
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.deposit(2, 1002, dec!(5)).unwrap();
+        txs.dispute(2, 1002).unwrap();
+        txs.set_disputes_enabled(false);
+        txs.set_overdraft_limit(1, dec!(100));
+        let capacity = txs.txs.capacity();
+
+        txs.clear();
+        assert_eq!(txs.get(1), None);
+        assert_eq!(txs.get(2), None);
+        assert_eq!(txs.txs.capacity(), capacity);
+        assert_eq!(txs.withdrawal(1, 1, dec!(1)), Err(Error::InsuffienctFunds));
+        txs.deposit(1, 1001, dec!(3)).unwrap();
+        assert_eq!(txs.deposit(1, 1000, dec!(3)), Err(Error::NonMonotonicTxid));
+        txs.dispute(1, 1001).unwrap();
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(0), dec!(3), false)));
+        assert_eq!(txs.txs.len(), 1);
+    }
+
+    #[test]
+    fn test_counts() {
+        let mut txs = Txs::new();
+        assert!(txs.is_empty());
+        assert_eq!(txs.account_count(), 0);
+        assert_eq!(txs.transaction_count(), 0);
+
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.deposit(2, 1002, dec!(5)).unwrap();
+        txs.deposit(1, 1003, dec!(1)).unwrap();
+        txs.dispute(1, 1001).unwrap();
+        txs.dispute(2, 1002).unwrap();
+        assert!(!txs.is_empty());
+        assert_eq!(txs.account_count(), 2);
+        assert_eq!(txs.transaction_count(), 3);
+
+        txs.clear();
+        assert!(txs.is_empty());
+    }
+
+    #[test]
+    fn test_is_disputed() {
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.withdrawal(1, 1002, dec!(1)).unwrap();
+        assert_eq!(txs.is_disputed(1001), Ok(false));
+        assert_eq!(txs.is_disputed(1002), Ok(false));
+        assert_eq!(txs.is_disputed(1003), Err(Error::TxNotFound));
+
+        txs.dispute(1, 1001).unwrap();
+        assert_eq!(txs.is_disputed(1001), Ok(true));
+        txs.resolve(1, 1001).unwrap();
+        assert_eq!(txs.is_disputed(1001), Ok(false));
+        txs.dispute(1, 1001).unwrap();
+        txs.charge_back(1, 1001).unwrap();
+        assert_eq!(txs.is_disputed(1001), Ok(false));
+    }
+
+    #[test]
+    fn test_disputed_txs() {
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.deposit(2, 1002, dec!(5)).unwrap();
+        txs.deposit(1, 1003, dec!(2.5)).unwrap();
+        txs.deposit(3, 1004, dec!(1)).unwrap();
+        txs.dispute(1, 1001).unwrap();
+        txs.dispute(2, 1002).unwrap();
+        txs.dispute(1, 1003).unwrap();
+        txs.resolve(1, 1003).unwrap();
+
+        let mut disputes: Vec<_> = txs.disputed_txs().collect();
+        disputes.sort();
+        assert_eq!(disputes, vec![(1001, 1, dec!(10)), (1002, 2, dec!(5))]);
+    }
+
+    #[test]
+    fn test_amount_transform() {
+        let mut txs = Txs::builder()
+            .amount_transform(Box::new(|amount| amount * dec!(2)))
+            .build();
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.withdrawal(1, 1002, dec!(3)).unwrap();
+        assert_eq!(txs.txs[&1001].amount, Some(dec!(20)));
+        assert_eq!(txs.txs[&1002].amount, Some(dec!(6)));
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(14), dec!(0), false)));
+
+        txs.dispute(1, 1001).unwrap();
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(-6), dec!(20), false)));
+    }
+
+    #[test]
+    fn test_set_disputes_enabled() {
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.deposit(1, 1002, dec!(5)).unwrap();
+        txs.dispute(1, 1001).unwrap();
+
+        txs.set_disputes_enabled(false);
+        assert_eq!(txs.dispute(1, 1002), Err(Error::DisputesDisabled));
+        assert_eq!(txs.txs[&1002].state, DisputeState::Undisputed);
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(5), dec!(10), false)));
+
+        txs.deposit(1, 1003, dec!(1)).unwrap();
+        txs.resolve(1, 1001).unwrap();
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(16), dec!(0), false)));
+
+        txs.set_disputes_enabled(true);
+        txs.dispute(1, 1002).unwrap();
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(11), dec!(5), false)));
+    }
+
+    #[test]
+    fn test_process_tracing() {
+        let mut txs = Txs::new();
+        let snapshot = |client, available, held, locked| {
+            Ok(AccountSnapshot {
+                client,
+                available,
+                held,
+                locked,
+            })
+        };
+        assert_eq!(
+            txs.process_tracing(vec![
+                Tx::deposit(1, 1001, dec!(10)),
+                Tx::deposit(2, 1002, dec!(3)),
+                Tx::withdrawal(1, 1003, dec!(4)),
+                Tx::dispute(1, 1001),
+                Tx::resolve(1, 1003),
+                Tx::charge_back(1, 1001),
+                Tx::deposit(1, 1004, dec!(1)),
+            ]),
+            vec![
+                (1001, snapshot(1, dec!(10), dec!(0), false)),
+                (1002, snapshot(2, dec!(3), dec!(0), false)),
+                (1003, snapshot(1, dec!(6), dec!(0), false)),
+                (1001, snapshot(1, dec!(-4), dec!(10), false)),
+                (1003, Err(Error::TxNotDisputed)),
+                (1001, snapshot(1, dec!(-4), dec!(0), true)),
+                (1004, Err(Error::AccountIsLocked)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_block_withdrawal_to_zero_with_held() {
+        for block in [true, false] {
+            let mut txs = Txs::builder()
+                .block_withdrawal_to_zero_with_held(block)
+                .build();
+            txs.deposit(1, 1001, dec!(4)).unwrap();
+            txs.deposit(1, 1002, dec!(6)).unwrap();
+            txs.dispute(1, 1001).unwrap();
+            txs.withdrawal(1, 1003, dec!(1)).unwrap();
+
+            let result = txs.withdrawal(1, 1004, dec!(5));
+            if block {
+                assert_eq!(result, Err(Error::WouldStrandHeldFunds));
+                assert_eq!(txs.get(1), Some(&Account::new(dec!(5), dec!(4), false)));
+            } else {
+                assert_eq!(result, Ok(()));
+                assert_eq!(txs.get(1), Some(&Account::new(dec!(0), dec!(4), false)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_unlock() {
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.deposit(1, 1002, dec!(5)).unwrap();
+        txs.dispute(1, 1001).unwrap();
+        txs.charge_back(1, 1001).unwrap();
+        assert_eq!(txs.deposit(1, 1003, dec!(1)), Err(Error::AccountIsLocked));
+
+        txs.unlock(1).unwrap();
+        txs.deposit(1, 1003, dec!(1)).unwrap();
+        txs.withdrawal(1, 1004, dec!(2)).unwrap();
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(4), dec!(0), false)));
+
+        assert_eq!(txs.unlock(2), Err(Error::AccountNotFound));
+        assert_eq!(txs.get(2), None);
+    }
+
+    #[test]
+    fn test_max_exposure() {
+        let mut txs = Txs::new();
+        assert_eq!(txs.max_exposure(), None);
+
+        txs.deposit(3, 1001, dec!(7)).unwrap();
+        txs.deposit(5, 1002, dec!(12)).unwrap();
+        txs.deposit(2, 1003, dec!(12)).unwrap();
+        txs.deposit(4, 1004, dec!(20)).unwrap();
+        txs.withdrawal(4, 1005, dec!(10)).unwrap();
+        assert_eq!(txs.max_exposure(), Some((2, dec!(12))));
+
+        txs.deposit(3, 1006, dec!(5.5)).unwrap();
+        txs.dispute(3, 1006).unwrap();
+        assert_eq!(txs.max_exposure(), Some((3, dec!(12.5))));
+    }
+
+    #[test]
+    fn test_account_not_found() {
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.dispute(1, 1001).unwrap();
+
+        assert_eq!(txs.get(2), None);
+        assert_eq!(txs.unlock(2), Err(Error::AccountNotFound));
+        assert_eq!(txs.consolidate(2, 1), Err(Error::AccountNotFound));
+        assert_eq!(txs.resolve_amount(2, dec!(1)), Err(Error::AccountNotFound));
+        assert_eq!(
+            txs.charge_back_prorata(2, dec!(1)),
+            Err(Error::AccountNotFound)
+        );
+        assert_eq!(txs.dispute(2, 1001), Err(Error::CidMismatch));
+        assert_eq!(txs.get(2), None);
+    }
+
+    #[test]
+    fn test_balance() {
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.withdrawal(1, 1002, dec!(4)).unwrap();
+        txs.deposit(1, 1003, dec!(3)).unwrap();
+        txs.dispute(1, 1003).unwrap();
+        assert_eq!(txs.balance(1), Ok((dec!(6), dec!(3), dec!(9))));
+        assert_eq!(txs.balance(2), Err(Error::AccountNotFound));
+
+        txs.accounts
+            .insert(3, Account::new(Decimal::MAX, dec!(1), false));
+        assert_eq!(txs.balance(3), Err(Error::MathError));
+    }
 
     #[test]
-    fn test_tx_not_found() {
+    fn test_strict_disputes() {
+        for strict in [false, true] {
+            let mut txs = Txs::builder().strict_disputes(strict).build();
+            txs.deposit(1, 1001, dec!(10)).unwrap();
+            txs.withdrawal(1, 1002, dec!(8)).unwrap();
+
+            let result = txs.dispute(1, 1001);
+            if strict {
+                assert_eq!(result, Err(Error::InsuffienctFunds));
+                assert_eq!(txs.get(1), Some(&Account::new(dec!(2), dec!(0), false)));
+                assert_eq!(txs.txs[&1001].state, DisputeState::Undisputed);
+            } else {
+                assert_eq!(result, Ok(()));
+                assert_eq!(txs.get(1), Some(&Account::new(dec!(-8), dec!(10), false)));
+                assert_eq!(txs.txs[&1001].state, DisputeState::Disputed);
+            }
+        }
+    }
+
+    #[test]
+    fn test_correction() {
         let mut txs = Txs::new();
-        assert_eq!(txs.dispute(1, 1001).unwrap_err(), Error::TxNotFound);
-        assert_eq!(txs.resolve(1, 1001).unwrap_err(), Error::TxNotFound);
-        assert_eq!(txs.charge_back(1, 1001).unwrap_err(), Error::TxNotFound);
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.withdrawal(1, 1002, dec!(3)).unwrap();
+
+        txs.correction(1, 1001, dec!(12.5)).unwrap();
+        assert_eq!(txs.txs[&1001].amount, Some(dec!(12.5)));
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(9.5), dec!(0), false)));
+
+        txs.correction(1, 1001, dec!(4)).unwrap();
+        assert_eq!(txs.txs[&1001].amount, Some(dec!(4)));
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(1), dec!(0), false)));
+
+        assert_eq!(
+            txs.correction(1, 1001, dec!(2)),
+            Err(Error::InsuffienctFunds)
+        );
+        assert_eq!(txs.correction(1, 1001, dec!(0)), Err(Error::InvalidAmount));
+        assert_eq!(
+            txs.correction(1, 1002, dec!(1)),
+            Err(Error::TxMustBeDeposit)
+        );
+        assert_eq!(txs.correction(1, 1003, dec!(1)), Err(Error::TxNotFound));
+
+        txs.dispute(1, 1001).unwrap();
+        assert_eq!(
+            txs.correction(1, 1001, dec!(5)),
+            Err(Error::TxAlreadyDisputed)
+        );
+        assert_eq!(txs.txs[&1001].amount, Some(dec!(4)));
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(-3), dec!(4), false)));
     }
 
     #[test]
-    fn test_cid_mismatch() {
+    fn test_correction_overdraft_limit() {
         let mut txs = Txs::new();
+        txs.set_overdraft_limit(1, dec!(100));
         txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.withdrawal(1, 1002, dec!(60)).unwrap();
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(-50), dec!(0), false)));
 
-        assert_eq!(txs.dispute(2, 1001).unwrap_err(), Error::CidMismatch);
-        assert_eq!(txs.resolve(2, 1001).unwrap_err(), Error::CidMismatch);
-        assert_eq!(txs.charge_back(2, 1001).unwrap_err(), Error::CidMismatch);
+        txs.correction(1, 1001, dec!(5)).unwrap();
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(-55), dec!(0), false)));
+        txs.withdrawal(1, 1003, dec!(44)).unwrap();
+        assert_eq!(
+            txs.correction(1, 1001, dec!(3)),
+            Err(Error::InsuffienctFunds)
+        );
+        txs.correction(1, 1001, dec!(4)).unwrap();
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(-100), dec!(0), false)));
+
+        let mut txs = Txs::builder().overdraft_limit(dec!(10)).build();
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.correction(1, 1001, dec!(0.5)).unwrap();
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(0.5), dec!(0), false)));
+        txs.withdrawal(1, 1002, dec!(10.5)).unwrap();
+        assert_eq!(
+            txs.correction(1, 1001, dec!(0.4)),
+            Err(Error::InsuffienctFunds)
+        );
     }
 
     #[test]
-    fn test_tx_not_disputed() {
+    fn test_held_underflow() {
         let mut txs = Txs::new();
-        txs.deposit(1, 1001, dec!(20)).unwrap();
-        assert_eq!(txs.resolve(1, 1001).unwrap_err(), Error::TxNotDisputed);
-        assert_eq!(txs.charge_back(1, 1001).unwrap_err(), Error::TxNotDisputed);
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.dispute(1, 1001).unwrap();
+        txs.accounts.get_mut(&1).unwrap().held = dec!(4);
+
+        assert_eq!(txs.resolve(1, 1001), Err(Error::HeldUnderflow));
+        assert_eq!(txs.charge_back(1, 1001), Err(Error::HeldUnderflow));
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(0), dec!(4), false)));
+        assert_eq!(txs.txs[&1001].state, DisputeState::Disputed);
+        assert_eq!(txs.txs[&1001].held, dec!(10));
+
+        txs.accounts.get_mut(&1).unwrap().held = dec!(10);
+        txs.charge_back(1, 1001).unwrap();
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(0), dec!(0), true)));
     }
 
     #[test]
-    fn test_total_overflow_when_deposit() {
+    fn test_ignore_zero_amount() {
+        let mut txs = Txs::new();
+        assert_eq!(txs.deposit(1, 1001, dec!(0)), Err(Error::InvalidAmount));
+
+        let mut txs = Txs::builder().ignore_zero_amount(true).build();
+        assert_eq!(txs.deposit(1, 1001, dec!(0)), Ok(()));
+        assert_eq!(txs.deposit(1, 1001, dec!(0.000)), Ok(()));
+        assert_eq!(txs.withdrawal(1, 1001, dec!(0)), Ok(()));
+        assert_eq!(txs.get(1), None);
+        assert!(txs.txs.is_empty());
+
+        txs.deposit(1, 1001, dec!(5)).unwrap();
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(5), dec!(0), false)));
+    }
+
+    #[test]
+    fn test_dispute_overflow() {
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.accounts.get_mut(&1).unwrap().held = Decimal::MAX;
+        assert_eq!(txs.dispute(1, 1001), Err(Error::MathError));
+        assert_eq!(
+            txs.get(1),
+            Some(&Account::new(dec!(10), Decimal::MAX, false))
+        );
+        assert_eq!(txs.txs[&1001].state, DisputeState::Undisputed);
+
+        txs.accounts.get_mut(&1).unwrap().held = dec!(0);
+        txs.accounts.get_mut(&1).unwrap().available = Decimal::MIN;
+        assert_eq!(txs.dispute(1, 1001), Err(Error::MathError));
+        assert_eq!(txs.txs[&1001].state, DisputeState::Undisputed);
+    }
+
+    #[test]
+    fn test_resolve_overflow() {
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.dispute(1, 1001).unwrap();
+        txs.accounts.get_mut(&1).unwrap().available = Decimal::MAX;
+        assert_eq!(txs.resolve(1, 1001), Err(Error::MathError));
+        assert_eq!(
+            txs.get(1),
+            Some(&Account::new(Decimal::MAX, dec!(10), false))
+        );
+        assert_eq!(txs.txs[&1001].state, DisputeState::Disputed);
+
+        txs.accounts.get_mut(&1).unwrap().held = Decimal::MIN;
+        assert_eq!(txs.charge_back(1, 1001), Err(Error::MathError));
+        assert_eq!(txs.txs[&1001].state, DisputeState::Disputed);
+    }
+
+    #[test]
+    fn test_accrue_interest() {
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, dec!(50)).unwrap();
+        txs.deposit(1, 1002, dec!(200)).unwrap();
+        txs.dispute(1, 1002).unwrap();
+        txs.deposit(2, 1003, dec!(30)).unwrap();
+        txs.deposit(3, 1004, dec!(40)).unwrap();
+        txs.deposit(3, 1005, dec!(40)).unwrap();
+        txs.dispute(3, 1004).unwrap();
+        txs.dispute(3, 1005).unwrap();
+        txs.charge_back(3, 1005).unwrap();
+
+        txs.accrue_interest(dec!(0.01)).unwrap();
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(52), dec!(200), false)));
+        assert_eq!(txs.get(2), Some(&Account::new(dec!(30), dec!(0), false)));
+        assert_eq!(txs.get(3), Some(&Account::new(dec!(0), dec!(40), true)));
+
+        txs.accounts.get_mut(&2).unwrap().held = dec!(1);
+        assert_eq!(txs.accrue_interest(Decimal::MAX), Err(Error::MathError));
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(52), dec!(200), false)));
+        assert_eq!(txs.get(2), Some(&Account::new(dec!(30), dec!(1), false)));
+    }
+
+    #[test]
+    fn test_clone() {
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.deposit(2, 1002, dec!(5)).unwrap();
+        txs.dispute(2, 1002).unwrap();
+
+        let snapshot = txs.clone();
+        txs.withdrawal(1, 1003, dec!(4)).unwrap();
+        txs.charge_back(2, 1002).unwrap();
+        txs.deposit(3, 1004, dec!(1)).unwrap();
+
+        assert_eq!(snapshot.txs.len(), 2);
+        assert_eq!(
+            snapshot.get(1),
+            Some(&Account::new(dec!(10), dec!(0), false))
+        );
+        assert_eq!(
+            snapshot.get(2),
+            Some(&Account::new(dec!(0), dec!(5), false))
+        );
+        assert_eq!(snapshot.get(3), None);
+        assert_eq!(snapshot.txs[&1002].state, DisputeState::Disputed);
+        assert_eq!(txs.get(2), Some(&Account::new(dec!(0), dec!(0), true)));
+    }
+
+    #[test]
+    fn test_snapshot_restore() {
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.deposit(1, 1002, dec!(5)).unwrap();
+        txs.withdrawal(1, 1003, dec!(2)).unwrap();
+        txs.deposit(2, 1004, dec!(3)).unwrap();
+        txs.dispute(2, 1004).unwrap();
+
+        let json = serde_json::to_vec(&txs.snapshot()).unwrap();
+        let mut restored = Txs::restore(serde_json::from_slice(&json).unwrap());
+
+        assert_eq!(restored.accounts, txs.accounts);
+        assert_eq!(restored.txs.len(), 4);
+        assert_eq!(restored.txs[&1004].state, DisputeState::Disputed);
+        assert_eq!(restored.next_seq, txs.next_seq);
+
+        restored.dispute(1, 1001).unwrap();
+        assert_eq!(
+            restored.get(1),
+            Some(&Account::new(dec!(3), dec!(10), false))
+        );
+        restored.resolve(2, 1004).unwrap();
+        assert_eq!(
+            restored.get(2),
+            Some(&Account::new(dec!(3), dec!(0), false))
+        );
+        assert_eq!(
+            restored.deposit(1, 1003, dec!(1)),
+            Err(Error::TxAlreadyExists)
+        );
+    }
+
+    #[test]
+    fn test_process_all() {
+        let mut txs = Txs::new();
+        assert_eq!(
+            txs.process_all(vec![
+                Tx::deposit(1, 1001, dec!(10)),
+                Tx::deposit(2, 1002, dec!(5)),
+                Tx::dispute(1, 1003),
+                Tx::withdrawal(2, 1004, dec!(1)),
+            ]),
+            vec![(2, Error::TxNotFound)]
+        );
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(10), dec!(0), false)));
+        assert_eq!(txs.get(2), Some(&Account::new(dec!(4), dec!(0), false)));
+        assert_eq!(txs.process_all(Vec::new()), vec![]);
+    }
+
+    #[test]
+    fn test_verify_solvency() {
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.deposit(1, 1002, dec!(5)).unwrap();
+        txs.deposit(2, 1003, dec!(3)).unwrap();
+        txs.deposit(3, 1004, dec!(7)).unwrap();
+        txs.dispute(1, 1001).unwrap();
+        txs.dispute(1, 1002).unwrap();
+        txs.resolve(1, 1002).unwrap();
+        txs.dispute(2, 1003).unwrap();
+        txs.charge_back(2, 1003).unwrap();
+        txs.dispute(3, 1004).unwrap();
+        txs.resolve_amount(3, dec!(2.5)).unwrap();
+        assert_eq!(txs.verify_solvency(), Ok(()));
+
+        txs.set_held(2, dec!(1));
+        txs.set_held(1, dec!(9));
+        assert_eq!(txs.verify_solvency(), Err(vec![1, 2]));
+    }
+
+    #[test]
+    fn test_audit() {
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.deposit(1, 1002, dec!(5)).unwrap();
+        txs.deposit(2, 1003, dec!(3)).unwrap();
+        txs.dispute(1, 1001).unwrap();
+        txs.dispute(1, 1002).unwrap();
+        txs.resolve(1, 1002).unwrap();
+        txs.dispute(2, 1003).unwrap();
+        txs.charge_back(2, 1003).unwrap();
+        assert_eq!(txs.audit(), Ok(()));
+
+        txs.set_held(2, dec!(1.5));
+        let err = txs.audit().unwrap_err();
+        assert_eq!(
+            err,
+            AuditError::HeldMismatch {
+                expected_held: dec!(10),
+                actual_held: dec!(11.5),
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "accounts hold 11.5 but disputed transactions hold 10"
+        );
+
         let mut txs = Txs::new();
         txs.deposit(1, 1001, Decimal::MAX).unwrap();
+        txs.deposit(2, 1002, Decimal::MAX).unwrap();
         txs.dispute(1, 1001).unwrap();
+        txs.dispute(2, 1002).unwrap();
+        let err = txs.audit().unwrap_err();
+        assert_eq!(err, AuditError::MathError);
+        assert_eq!(err.to_string(), "arithmetic overflow in held funds");
+    }
 
-        assert_eq!(txs.deposit(1, 1002, dec!(1)), Err(Error::MathError));
+    #[test]
+    fn test_from_iter() {
+        let txs: Txs = vec![
+            Tx::dispute(1, 1001),
+            Tx::deposit(1, 1001, dec!(10)),
+            Tx::deposit(1, 1002, dec!(2.5)),
+            Tx::dispute(1, 1001),
+            Tx::withdrawal(1, 1003, dec!(5)),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(2.5), dec!(10), false)));
+        assert_eq!(txs.txs[&1001].state, DisputeState::Disputed);
+        assert!(!txs.txs.contains_key(&1003));
     }
 
     #[test]
-    fn test_account_locked() {
+    fn test_disputable_window() {
+        let mut txs = Txs::builder().disputable_window(1).build();
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.deposit(2, 1002, dec!(1)).unwrap();
+        txs.deposit(1, 1003, dec!(5)).unwrap();
+        txs.withdrawal(1, 1004, dec!(1)).unwrap();
+        txs.deposit(2, 1005, dec!(1)).unwrap();
+
+        assert_eq!(txs.dispute(1, 1001), Err(Error::DisputeWindowExpired));
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(14), dec!(0), false)));
+        txs.dispute(1, 1003).unwrap();
+        assert_eq!(txs.dispute(1, 1003), Err(Error::TxAlreadyDisputed));
+        assert_eq!(txs.dispute(1, 1002), Err(Error::CidMismatch));
+        assert_eq!(txs.dispute(2, 1002), Err(Error::DisputeWindowExpired));
+        txs.dispute(2, 1005).unwrap();
+
+        let mut txs = Txs::builder().disputable_window(2).build();
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.deposit(1, 1002, dec!(5)).unwrap();
+        txs.dispute(1, 1001).unwrap();
+    }
+
+    #[test]
+    fn test_disputable_window_tracking() {
+        let mut txs = Txs::builder()
+            .disputable_window(2)
+            .allow_txid_reuse_after_chargeback(true)
+            .no_lock_clients([1])
+            .build();
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.deposit(1, 1002, dec!(5)).unwrap();
+        txs.dispute(1, 1002).unwrap();
+        txs.charge_back(1, 1002).unwrap();
+        txs.deposit(1, 1002, dec!(5)).unwrap();
+        txs.dispute(1, 1001).unwrap();
+        txs.resolve(1, 1001).unwrap();
+
+        txs.deposit(2, 1003, dec!(1)).unwrap();
+        txs.consolidate(2, 1).unwrap();
+        assert_eq!(txs.dispute(1, 1001), Err(Error::DisputeWindowExpired));
+        txs.dispute(1, 1002).unwrap();
+        txs.dispute(1, 1003).unwrap();
+    }
+
+    #[test]
+    fn test_allow_txid_reuse_after_chargeback() {
         let mut txs = Txs::new();
         txs.deposit(1, 1001, dec!(10)).unwrap();
         txs.dispute(1, 1001).unwrap();
         txs.charge_back(1, 1001).unwrap();
+        assert_eq!(txs.deposit(2, 1001, dec!(8)), Err(Error::TxAlreadyExists));
+
+        let mut txs = Txs::builder()
+            .allow_txid_reuse_after_chargeback(true)
+            .no_lock_clients([1])
+            .build();
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.deposit(1, 1002, dec!(5)).unwrap();
+        assert_eq!(txs.deposit(1, 1002, dec!(5)), Err(Error::TxAlreadyExists));
+        txs.dispute(1, 1001).unwrap();
+        txs.charge_back(1, 1001).unwrap();
+
+        txs.deposit(1, 1001, dec!(8)).unwrap();
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(13), dec!(0), false)));
+        assert_eq!(txs.txs[&1001].amount, Some(dec!(8)));
+        assert_eq!(txs.txs[&1001].state, DisputeState::Undisputed);
+        assert_eq!(txs.deposit(1, 1001, dec!(8)), Err(Error::TxAlreadyExists));
+        txs.dispute(1, 1001).unwrap();
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(5), dec!(8), false)));
+    }
+
+    #[test]
+    fn test_conservation_report() {
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.deposit(1, 1002, dec!(5)).unwrap();
+        txs.deposit(2, 1003, dec!(7)).unwrap();
+        txs.withdrawal(1, 1004, dec!(3)).unwrap();
+        txs.correction(2, 1003, dec!(8)).unwrap();
+        txs.dispute(1, 1002).unwrap();
+        txs.charge_back(1, 1002).unwrap();
+        txs.dispute(2, 1003).unwrap();
 
-        assert_eq!(txs.deposit(1, 1002, dec!(5)), Err(Error::AccountIsLocked));
         assert_eq!(
-            txs.withdrawal(1, 1002, dec!(5)),
+            txs.conservation_report(),
+            Ok(ConservationReport {
+                deposited: dec!(23),
+                withdrawn: dec!(3),
+                charged_back: dec!(5),
+                available: dec!(7),
+                held: dec!(8),
+                balanced: true,
+            })
+        );
+
+        txs.set_held(2, dec!(1));
+        assert!(!txs.conservation_report().unwrap().balanced);
+
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, Decimal::MAX).unwrap();
+        txs.deposit(2, 1002, Decimal::MAX).unwrap();
+        assert_eq!(txs.conservation_report(), Err(Error::MathError));
+    }
+
+    #[test]
+    fn test_conservation_report_partial_chargeback() {
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.dispute(1, 1001).unwrap();
+        txs.resolve_amount(1, dec!(4)).unwrap();
+        txs.charge_back(1, 1001).unwrap();
+
+        let report = txs.conservation_report().unwrap();
+        assert_eq!(report.charged_back, dec!(6));
+        assert_eq!(report.available, dec!(4));
+        assert!(report.balanced);
+
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.deposit(1, 1002, dec!(5)).unwrap();
+        txs.dispute(1, 1001).unwrap();
+        txs.dispute(1, 1002).unwrap();
+        txs.charge_back_prorata(1, dec!(3)).unwrap();
+
+        let report = txs.conservation_report().unwrap();
+        assert_eq!(report.charged_back, dec!(3));
+        assert_eq!(report.held, dec!(12));
+        assert!(report.balanced);
+        assert_eq!(
+            Txs::restore(txs.snapshot()).conservation_report(),
+            Ok(report)
+        );
+    }
+
+    #[test]
+    fn test_dispute_charged_back() {
+        let mut txs = Txs::builder().no_lock_clients([1]).build();
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.deposit(1, 1002, dec!(20)).unwrap();
+        txs.dispute(1, 1001).unwrap();
+        txs.charge_back(1, 1001).unwrap();
+        assert_eq!(txs.dispute(1, 1001), Err(Error::AlreadyChargedBack));
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(20), dec!(0), false)));
+
+        let mut txs = Txs::new();
+        txs.deposit(2, 1003, dec!(10)).unwrap();
+        txs.dispute(2, 1003).unwrap();
+        txs.charge_back(2, 1003).unwrap();
+        txs.unlock(2).unwrap();
+        assert_eq!(txs.dispute(2, 1003), Err(Error::AlreadyChargedBack));
+    }
+
+    #[test]
+    fn test_single_dispute() {
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.dispute(1, 1001).unwrap();
+        txs.resolve(1, 1001).unwrap();
+        txs.dispute(1, 1001).unwrap();
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(0), dec!(10), false)));
+
+        let mut txs = Txs::builder().single_dispute(true).build();
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.deposit(1, 1002, dec!(5)).unwrap();
+        txs.dispute(1, 1001).unwrap();
+        assert_eq!(txs.dispute(1, 1001), Err(Error::TxAlreadyDisputed));
+        txs.resolve(1, 1001).unwrap();
+        assert_eq!(txs.dispute(1, 1001), Err(Error::TxAlreadyDisputed));
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(15), dec!(0), false)));
+        txs.dispute(1, 1002).unwrap();
+    }
+
+    #[test]
+    fn test_tx_kind_display() {
+        for (kind, token) in [
+            (TxKind::Deposit, "deposit"),
+            (TxKind::Withdrawal, "withdrawal"),
+            (TxKind::Dispute, "dispute"),
+            (TxKind::Resolve, "resolve"),
+            (TxKind::ChargeBack, "chargeback"),
+            (TxKind::Correction, "correction"),
+            (TxKind::Freeze, "freeze"),
+        ] {
+            assert_eq!(kind.to_string(), token);
+            assert_eq!(token.parse(), Ok(kind));
+            assert_eq!(serde_json::to_value(kind).unwrap(), token);
+        }
+        for token in ["", "Deposit", "refund"] {
+            assert_eq!(token.parse::<TxKind>(), Err(Error::InvalidTx));
+        }
+    }
+
+    #[test]
+    fn test_error_hash() {
+        let mut errors = HashSet::new();
+        errors.insert(Error::TxAlreadyExists);
+        errors.insert(Error::TxAlreadyExists);
+        assert_eq!(errors.len(), 1);
+
+        errors.insert(Error::IdOutOfRange(IdField::Client));
+        errors.insert(Error::IdOutOfRange(IdField::Tx));
+        errors.insert(Error::IdOutOfRange(IdField::Client));
+        assert_eq!(errors.len(), 3);
+    }
+
+    #[test]
+    fn test_overdraft_limit() {
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, dec!(50)).unwrap();
+        txs.deposit(2, 1002, dec!(50)).unwrap();
+        txs.set_overdraft_limit(1, dec!(100));
+
+        assert_eq!(
+            txs.withdrawal(1, 1003, dec!(150.01)),
+            Err(Error::InsuffienctFunds)
+        );
+        txs.withdrawal(1, 1003, dec!(150)).unwrap();
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(-100), dec!(0), false)));
+        assert_eq!(
+            txs.withdrawal(1, 1004, dec!(0.01)),
+            Err(Error::InsuffienctFunds)
+        );
+
+        assert_eq!(
+            txs.withdrawal(2, 1005, dec!(50.01)),
+            Err(Error::InsuffienctFunds)
+        );
+
+        let restored = Txs::restore(txs.snapshot());
+        assert_eq!(restored.overdraft_limits, txs.overdraft_limits);
+    }
+
+    #[test]
+    fn test_default_overdraft_limit() {
+        let mut txs = Txs::builder().overdraft_limit(dec!(20)).build();
+        txs.set_overdraft_limit(2, dec!(5));
+
+        txs.withdrawal(1, 1001, dec!(20)).unwrap();
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(-20), dec!(0), false)));
+        assert_eq!(
+            txs.withdrawal(1, 1002, dec!(0.01)),
+            Err(Error::InsuffienctFunds)
+        );
+
+        assert_eq!(
+            txs.withdrawal(2, 1003, dec!(5.01)),
+            Err(Error::InsuffienctFunds)
+        );
+        txs.withdrawal(2, 1003, dec!(5)).unwrap();
+        assert_eq!(txs.get(2), Some(&Account::new(dec!(-5), dec!(0), false)));
+    }
+
+    #[test]
+    fn test_freeze() {
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+        txs.deposit(1, 1002, dec!(5)).unwrap();
+
+        txs.freeze(1).unwrap();
+        txs.freeze(1).unwrap();
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(15), dec!(0), true)));
+        assert_eq!(txs.deposit(1, 1003, dec!(1)), Err(Error::AccountIsLocked));
+        assert_eq!(
+            txs.withdrawal(1, 1003, dec!(1)),
             Err(Error::AccountIsLocked)
         );
         assert_eq!(txs.dispute(1, 1001), Err(Error::AccountIsLocked));
-        assert_eq!(txs.resolve(1, 1001), Err(Error::AccountIsLocked));
-        assert_eq!(txs.charge_back(1, 1001), Err(Error::AccountIsLocked));
+        assert_eq!(txs.freeze(2), Err(Error::AccountNotFound));
+        assert_eq!(
+            txs.process_tx(Tx::new(TxKind::Freeze, 1, 0, Some(dec!(1)))),
+            Err(Error::AccountIsLocked)
+        );
+
+        txs.unlock(1).unwrap();
+        txs.withdrawal(1, 1003, dec!(1)).unwrap();
+        txs.dispute(1, 1001).unwrap();
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(4), dec!(10), false)));
+    }
+
+    #[test]
+    fn test_simulate() {
+        let mut txs = Txs::new();
+        txs.deposit(1, 1001, dec!(10)).unwrap();
+
+        assert_eq!(
+            txs.simulate(Tx::deposit(1, 1002, dec!(2.5))),
+            Ok(AccountSnapshot {
+                client: 1,
+                available: dec!(12.5),
+                held: dec!(0),
+                locked: false
+            })
+        );
+        assert_eq!(
+            txs.simulate(Tx::dispute(1, 1001))
+                .map(|snapshot| snapshot.held),
+            Ok(dec!(10))
+        );
+        assert_eq!(
+            txs.simulate(Tx::withdrawal(1, 1002, dec!(11))),
+            Err(Error::InsuffienctFunds)
+        );
+        assert_eq!(
+            txs.simulate(Tx::deposit(2, 1002, dec!(1)))
+                .map(|snapshot| snapshot.client),
+            Ok(2)
+        );
+
+        assert_eq!(txs.get(1), Some(&Account::new(dec!(10), dec!(0), false)));
+        assert_eq!(txs.get(2), None);
+        assert_eq!(txs.txs.len(), 1);
+        txs.deposit(1, 1002, dec!(1)).unwrap();
     }
 }
@@ -2,18 +2,71 @@ use std::{env, error::Error, fs::File, io, process};
 
 use toy_payments_engine::csv::{process_transactions, write_transactions};
 
+fn usage() -> ! {
+    eprintln!(
+        "Usage: {} [--summary] [--output <path>] <path-to-transactions.csv | ->",
+        env!("CARGO_BIN_NAME")
+    );
+    process::exit(exitcode::USAGE);
+}
+
+/// Whether `err`, or any of its sources, is caused by a closed output pipe,
+/// _e.g._, when the output is piped to `head`.
+fn is_broken_pipe(err: &(dyn Error + 'static)) -> bool {
+    let mut source = Some(err);
+    while let Some(err) = source {
+        let io_err = match err.downcast_ref::<csv::Error>().map(csv::Error::kind) {
+            Some(csv::ErrorKind::Io(io_err)) => Some(io_err),
+            _ => err.downcast_ref::<io::Error>(),
+        };
+        if io_err.is_some_and(|err| err.kind() == io::ErrorKind::BrokenPipe) {
+            return true;
+        }
+        source = err.source();
+    }
+    false
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
-    let args: Vec<String> = env::args().collect();
+    let mut summary = false;
+    let mut output = None;
+    let mut paths = vec![];
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--summary" {
+            summary = true;
+        } else if arg == "--output" || arg == "-o" {
+            output = Some(args.next().unwrap_or_else(|| usage()));
+        } else {
+            paths.push(arg);
+        }
+    }
 
-    if args.len() == 2 {
-        let file = File::open(&args[1])?;
-        let txs = process_transactions(file)?;
-        write_transactions(&txs, io::stdout())
+    if let [path] = paths.as_slice() {
+        let (txs, report) = if path == "-" {
+            process_transactions(io::stdin().lock())?
+        } else {
+            process_transactions(File::open(path)?)?
+        };
+        if let Some(output) = output {
+            write_transactions(&txs, File::create(output)?)?;
+        } else if let Err(err) = write_transactions(&txs, io::stdout()) {
+            if is_broken_pipe(err.as_ref()) {
+                return Ok(());
+            }
+            return Err(err);
+        }
+        if summary {
+            eprintln!(
+                "processed={} skipped={} accounts={} locked={}",
+                report.processed,
+                report.errors.len(),
+                txs.accounts().count(),
+                txs.accounts().filter(|(_, account)| account.locked).count()
+            );
+        }
+        Ok(())
     } else {
-        eprintln!(
-            "Usage: {} <path-to-transactions.csv>",
-            env!("CARGO_BIN_NAME")
-        );
-        process::exit(exitcode::USAGE);
+        usage();
     }
 }
@@ -1,7 +1,10 @@
-use std::process::Command;
+use std::{
+    env, fs,
+    process::{Command, Stdio},
+};
 
 use assert_cmd::prelude::{CommandCargoExt, OutputAssertExt};
-use predicates::prelude::predicate;
+use predicates::prelude::{predicate, PredicateBooleanExt};
 
 fn bin() -> Command {
     Command::cargo_bin("toy-payments-engine").unwrap()
@@ -42,3 +45,79 @@ fn path_exist() {
         .success()
         .stdout(predicate::str::contains("client,available,held,total,locked"));
 }
+
+#[test]
+fn summary_on_stderr() {
+    bin()
+        .arg("--summary")
+        .arg("./input-example.csv")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("processed=").not())
+        .stderr(predicate::str::contains(
+            "processed=9 skipped=1 accounts=2 locked=1",
+        ));
+}
+
+#[test]
+fn read_from_stdin() {
+    assert_cmd::Command::cargo_bin("toy-payments-engine")
+        .unwrap()
+        .arg("-")
+        .write_stdin("type, client, tx, amount\ndeposit, 1, 1, 1.5\ndeposit, 2, 2, 2.0\n")
+        .assert()
+        .success()
+        .stdout("client,available,held,total,locked\n1,1.5,0,1.5,false\n2,2,0,2,false\n");
+}
+
+#[test]
+fn usage_with_output_without_path() {
+    bin()
+        .arg("./input-example.csv")
+        .arg("--output")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Usage: "));
+}
+
+#[test]
+fn output_to_file() {
+    let path = env::temp_dir().join(format!("toy-payments-engine-{}.csv", std::process::id()));
+    bin()
+        .arg("-o")
+        .arg(&path)
+        .arg("./input-example.csv")
+        .assert()
+        .success()
+        .stdout("");
+    let contents = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert!(contents.starts_with("client,available,held,total,locked\n"));
+    assert_eq!(contents.lines().count(), 3);
+}
+
+#[test]
+fn broken_pipe_exits_cleanly() {
+    let path = env::temp_dir().join(format!(
+        "toy-payments-engine-pipe-{}.csv",
+        std::process::id()
+    ));
+    let mut data = String::from("type, client, tx, amount\n");
+    for client in 1..=20000 {
+        data.push_str(&format!("deposit, {}, {}, 1.0\n", client, client));
+    }
+    fs::write(&path, data).unwrap();
+
+    let mut child = bin()
+        .arg(&path)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    drop(child.stdout.take());
+    let output = child.wait_with_output().unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "");
+}